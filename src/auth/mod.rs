@@ -1,16 +1,31 @@
+mod oidc;
 mod routers;
 mod schema;
 
 use tide::Server;
 
+use crate::middleware::LoginMiddleware;
 use crate::State;
+pub(crate) use routers::verify_mfa;
 pub(crate) use schema::Register;
 
 pub fn auth_router(app: &mut Server<State>) {
     let mut auth = app.at("/auth");
     auth.at("/login").post(routers::login);
+    auth.at("/login/mfa").post(routers::login_mfa);
+    auth.at("/magic").post(routers::magic);
+    auth.at("/magic/confirm").post(routers::magic_confirm);
+    auth.at("/oidc/:provider").get(routers::oidc_login);
+    auth.at("/oidc/:provider/callback").post(routers::oidc_callback);
+    auth.at("/refresh").post(routers::refresh);
+    auth.at("/logout").post(routers::logout);
     auth.at("/register").post(routers::register);
     auth.at("/resend").post(routers::resend);
     auth.at("/confirm").post(routers::confirm);
-    auth.at("/resetpwd").post(routers::reset_pwd);
+    auth.at("/email/confirm").post(routers::email_confirm);
+    auth.at("/email/cancel").post(routers::email_cancel);
+    auth.at("/resetpwd")
+        .with(LoginMiddleware)
+        .post(routers::reset_pwd);
+    auth.at("/resetpwd/confirm").post(routers::reset_pwd_confirm);
 }
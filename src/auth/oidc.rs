@@ -0,0 +1,186 @@
+use data_encoding::BASE64URL_NOPAD;
+use ring::digest;
+use surf::{Body, Url};
+
+use crate::setting::OidcProvider;
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+// userinfo 接口返回的标准字段
+#[derive(Deserialize, Debug)]
+pub(crate) struct OidcClaims {
+    pub(crate) sub: String,
+    pub(crate) email: Option<String>,
+    #[serde(default)]
+    pub(crate) email_verified: bool,
+    pub(crate) name: Option<String>,
+    pub(crate) preferred_username: Option<String>,
+}
+
+impl OidcClaims {
+    // 只有身份提供方确认过的邮箱才能用来匹配帐号
+    pub(crate) fn verified_email(&self) -> Option<String> {
+        self.email
+            .as_ref()
+            .filter(|_| self.email_verified)
+            .map(|e| e.to_lowercase())
+    }
+
+    pub(crate) fn username(&self, email: &str) -> String {
+        self.preferred_username
+            .clone()
+            .or_else(|| self.name.clone())
+            .unwrap_or_else(|| email.split('@').next().unwrap_or(email).to_string())
+    }
+}
+
+// PKCE S256: base64url(sha256(verifier))
+pub(crate) fn pkce_challenge(verifier: &str) -> String {
+    BASE64URL_NOPAD.encode(digest::digest(&digest::SHA256, verifier.as_bytes()).as_ref())
+}
+
+pub(crate) fn authorize_url(
+    provider: &OidcProvider,
+    state: &str,
+    verifier: &str,
+) -> Result<String, String> {
+    let mut url = Url::parse(&provider.authorize_url).map_err(|e| e.to_string())?;
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &provider.client_id)
+        .append_pair("redirect_uri", &provider.redirect_uri)
+        .append_pair("scope", &provider.scopes.join(" "))
+        .append_pair("state", state)
+        .append_pair("code_challenge", &pkce_challenge(verifier))
+        .append_pair("code_challenge_method", "S256");
+    Ok(url.to_string())
+}
+
+// 用授权码换取 access token，再从 userinfo 接口取得用户信息
+pub(crate) async fn exchange(
+    provider: &OidcProvider,
+    code: &str,
+    verifier: &str,
+) -> Result<OidcClaims, String> {
+    let form = [
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", &provider.redirect_uri),
+        ("client_id", &provider.client_id),
+        ("client_secret", &provider.client_secret),
+        ("code_verifier", verifier),
+    ];
+    let body = Body::from_form(&form).map_err(|e| e.to_string())?;
+    let mut res = surf::post(&provider.token_url)
+        .header("Accept", "application/json")
+        .body(body)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !res.status().is_success() {
+        return Err(format!("token endpoint returned {}", res.status()));
+    }
+    let token: TokenResponse = res.body_json().await.map_err(|e| e.to_string())?;
+
+    let mut res = surf::get(&provider.userinfo_url)
+        .header("Authorization", format!("Bearer {}", token.access_token))
+        .await
+        .map_err(|e| e.to_string())?;
+    if !res.status().is_success() {
+        return Err(format!("userinfo endpoint returned {}", res.status()));
+    }
+    res.body_json().await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use async_std::net::TcpListener;
+    use serde_json::json;
+    use tide::{Request, StatusCode};
+
+    use super::{authorize_url, exchange, pkce_challenge};
+    use crate::setting::OidcProvider;
+
+    const VERIFIER: &str = "dBjftJeZ4CVP-mJ0kuTApXYQv8TEmmRIqgUSS-wPDLq8M";
+
+    #[derive(Deserialize)]
+    struct TokenForm {
+        grant_type: String,
+        code: String,
+        code_verifier: String,
+    }
+
+    async fn token(mut req: Request<()>) -> tide::Result {
+        let form: TokenForm = req.body_form().await?;
+        if form.grant_type != "authorization_code" || form.code != "good" {
+            return Ok(StatusCode::BadRequest.into());
+        }
+        if pkce_challenge(&form.code_verifier) != pkce_challenge(VERIFIER) {
+            return Ok(StatusCode::BadRequest.into());
+        }
+        Ok(json!({ "access_token": "at-1", "token_type": "Bearer" }).into())
+    }
+
+    async fn userinfo(req: Request<()>) -> tide::Result {
+        match req.header("Authorization").map(|h| h.as_str()) {
+            Some("Bearer at-1") => Ok(json!({
+                "sub": "42",
+                "email": "Lomect@Example.com",
+                "email_verified": true,
+                "preferred_username": "lomect"
+            })
+            .into()),
+            _ => Ok(StatusCode::Unauthorized.into()),
+        }
+    }
+
+    fn provider(base: &str) -> OidcProvider {
+        OidcProvider {
+            name: String::from("local"),
+            client_id: String::from("client"),
+            client_secret: String::from("secret"),
+            authorize_url: format!("{}/authorize", base),
+            token_url: format!("{}/token", base),
+            userinfo_url: format!("{}/userinfo", base),
+            redirect_uri: String::from("http://app/callback"),
+            scopes: vec![String::from("openid"), String::from("email")],
+        }
+    }
+
+    #[test]
+    fn test_authorize_url() {
+        assert_eq!(
+            pkce_challenge(VERIFIER),
+            "TtIoXbhSE6AyNvZHcxX3mC6Jum83aPjtpCMvbtxB3Yc"
+        );
+        let url = authorize_url(&provider("http://idp"), "st", VERIFIER).unwrap();
+        assert_eq!(
+            url,
+            "http://idp/authorize?response_type=code&client_id=client\
+             &redirect_uri=http%3A%2F%2Fapp%2Fcallback&scope=openid+email&state=st\
+             &code_challenge=TtIoXbhSE6AyNvZHcxX3mC6Jum83aPjtpCMvbtxB3Yc\
+             &code_challenge_method=S256"
+        );
+    }
+
+    #[async_std::test]
+    async fn test_exchange() {
+        let mut app = tide::new();
+        app.at("/token").post(token);
+        app.at("/userinfo").get(userinfo);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        async_std::task::spawn(app.listen(listener));
+
+        let idp = provider(&format!("http://{}", addr));
+        let claims = exchange(&idp, "good", VERIFIER).await.unwrap();
+        assert_eq!(claims.sub, "42");
+        assert_eq!(claims.verified_email().as_deref(), Some("lomect@example.com"));
+        assert_eq!(claims.username("lomect@example.com"), "lomect");
+
+        assert!(exchange(&idp, "bad", VERIFIER).await.is_err());
+        assert!(exchange(&idp, "good", "other").await.is_err());
+    }
+}
@@ -1,15 +1,83 @@
-use mongodb::bson::{doc, from_document, oid::ObjectId};
-use redis::AsyncCommands;
+use std::convert::TryFrom;
+use std::net::{IpAddr, SocketAddr};
+
+use async_std::task;
+use chrono::prelude::{Local, Utc};
+use mongodb::bson::{doc, from_document, oid::ObjectId, to_bson, Bson};
+use tide::http::cookies::{Cookie, SameSite};
 use tide::{log, prelude::*, Request};
-use validator::Validate;
+use validator::{Validate, ValidationErrors};
 
-use super::schema::{Login, Register, Resend, ResetPwd};
-use crate::db::Options;
-use crate::middleware::Token;
-use crate::models::{User, USER};
-use crate::utils::{hash_password, password_verify, send_email, status, Responser};
+use super::oidc;
+use super::schema::{
+    Login, LoginMfa, MagicLink, MfaPending, OidcCallback, OidcRedirect, Refresh, Register, Resend,
+    ResetPwd, ResetPwdConfirm,
+};
+use crate::db::{
+    activate_verified, find_user, is_duplicate, MongoDb, Options, Session, CONFIRM_EXPIRE_TIME,
+    LOGIN_LOCK_TIME, MAGIC_EXPIRE_TIME, MFA_EXPIRE_TIME, OIDC_EXPIRE_TIME, RESET_EXPIRE_TIME,
+};
+use crate::middleware::{bearer, session_owner, Token};
+use crate::models::{
+    Identity, Mfa, PasswordHistory, User, IDENTITY, PASSWORD_HISTORY, USER,
+};
+use crate::utils::{
+    hash_password, needs_rehash, password, password_verify, rand_str, send_email, status,
+    Responser,
+};
 use crate::{State, CONFIG};
 
+// 两步验证码最多尝试次数
+const MFA_MAX_ATTEMPTS: usize = 5;
+
+// 第三方登录时绑定 state 的 cookie
+const OIDC_STATE_COOKIE: &str = "oidc_state";
+
+lazy_static! {
+    // 帐号不存在时也做一次密码校验，响应时间与密码错误一致
+    // argon2 参数在启动时已经校验过，这里不会失败
+    static ref DUMMY_HASH: String = hash_password(&rand_str(16)).unwrap_or_default();
+}
+
+// 客户端 IP，对端地址带端口，只保留 IP
+fn client_ip(req: &Request<State>) -> Option<String> {
+    let peer = req.peer_addr()?;
+    let peer = match peer.parse::<SocketAddr>() {
+        Ok(addr) => addr.ip(),
+        Err(_) => return Some(peer.to_string()),
+    };
+    let forwarded = req
+        .header("X-Forwarded-For")
+        .map(|h| h.iter().map(|v| v.as_str()).collect::<Vec<_>>().join(","))
+        .unwrap_or_default();
+    Some(forwarded_ip(peer, &forwarded, &CONFIG.server.trusted_proxies).to_string())
+}
+
+// 直连的不是受信任的代理时 X-Forwarded-For 可以伪造，直接用对端地址；
+// 否则从右往左跳过受信任的代理，第一个不受信任的地址为客户端
+fn forwarded_ip(peer: IpAddr, forwarded: &str, trusted: &[IpAddr]) -> IpAddr {
+    let mut ip = peer;
+    if !trusted.contains(&ip) {
+        return ip;
+    }
+    for hop in forwarded.rsplit(',') {
+        match hop.trim().parse::<IpAddr>() {
+            Ok(addr) => ip = addr,
+            Err(_) => break,
+        }
+        if !trusted.contains(&ip) {
+            break;
+        }
+    }
+    ip
+}
+
+// 记录登录设备信息
+fn new_session(req: &Request<State>) -> Session {
+    let user_agent = req.header("User-Agent").map(|h| h.as_str().to_string());
+    Session::new(user_agent, client_ip(req))
+}
+
 pub(crate) async fn login(mut req: Request<State>) -> tide::Result {
     let req_data: Login = req.body_json().await?;
     if let Err(e) = req_data.validate() {
@@ -18,49 +86,379 @@ pub(crate) async fn login(mut req: Request<State>) -> tide::Result {
 
     let mongo_col = &req.state().mongo;
     let redis_cli = req.state().redis.clone();
-    let mut redis_con = redis_cli.connection().await?;
+    let email = req_data.email.to_lowercase();
+    let ip = client_ip(&req);
+    let wait = redis_cli.login_wait(&email, ip.as_deref()).await?;
+    if wait > 0 {
+        let msg = format!("尝试次数过多，请 {} 秒后再试", wait);
+        return Responser::new(Some(msg), &status::TOO_MANY).to_result();
+    }
+
     // 查询帐号
     let mut opt = Options::default();
-    opt.find_one_opt(&USER, Some(doc! { "email": req_data.email }));
+    opt.find_one_opt(&USER, Some(doc! { "email": &email }));
+    let user = match mongo_col.find(opt).await?.pop() {
+        Some(data) => Some((data.get_object_id("_id")?.to_hex(), from_document::<User>(data)?)),
+        None => None,
+    };
 
-    let mut user_doc = mongo_col.find(opt).await?;
-    let data = match user_doc.pop() {
-        Some(d) => d,
+    // 帐号不存在和密码错误返回相同的结果
+    let (id, user) = match user {
+        Some((id, user)) if password_verify(&user.password, &req_data.password) => (id, user),
+        user => {
+            if user.is_none() {
+                password_verify(&DUMMY_HASH, &req_data.password);
+            }
+            let locked = redis_cli.login_failed(&email, ip.as_deref()).await?;
+            if let (true, Some((_, user))) = (locked, user) {
+                lock_notify(user.email);
+            }
+            return Responser::new(Some("帐号或密码错误"), &status::UNAUTH).to_result();
+        }
+    };
+    // 开启两步验证时，第二步通过后才清除失败记录
+    if !user.mfa.as_ref().map(|m| m.enabled).unwrap_or(false) {
+        redis_cli.login_succeeded(&email).await?;
+    }
+
+    // 旧格式或参数已调整的 hash，用本次的明文密码重新生成
+    if needs_rehash(&user.password) {
+        let mut opt = Options::default();
+        opt.update_opt(&USER, Some(doc! { "_id": ObjectId::with_string(&id)? }), None);
+        let password = hash_password(&req_data.password)?;
+        mongo_col.update(doc! { "$set": { "password": password } }, opt).await?;
+    }
+    issue_session(&req, id, &user).await
+}
+
+// 开启两步验证时先返回 mfa_token，否则直接生成 Session
+async fn issue_session(req: &Request<State>, id: String, user: &User) -> tide::Result {
+    let redis_cli = &req.state().redis;
+    if user.mfa.as_ref().map(|m| m.enabled).unwrap_or(false) {
+        let mfa_token = redis_cli.set_once_token("mfa", &id, *MFA_EXPIRE_TIME).await?;
+        let pending = MfaPending {
+            mfa_token,
+            expires_in: *MFA_EXPIRE_TIME,
+        };
+        return Responser::new(Some(pending), &status::MFA_REQUIRED).to_result();
+    }
+    let token = redis_cli.set_token(id, &user.roles, new_session(req)).await?;
+    Responser::new(Some(token), &status::OK).to_result()
+}
+
+// 校验两步验证码，失败次数与密码错误共用登录计数，校验失败时返回错误响应
+pub(crate) async fn verify_mfa(
+    req: &Request<State>,
+    user: &User,
+    code: &str,
+    failed: &status::Res,
+) -> tide::Result<Result<Mfa, tide::Response>> {
+    let redis_cli = &req.state().redis;
+    let email = user.email.to_lowercase();
+    let ip = client_ip(req);
+    let wait = redis_cli.login_wait(&email, ip.as_deref()).await?;
+    if wait > 0 {
+        let msg = format!("尝试次数过多，请 {} 秒后再试", wait);
+        return Ok(Err(Responser::new(Some(msg), &status::TOO_MANY).to_result()?));
+    }
+
+    let now = Utc::now().timestamp() as u64;
+    let mfa = user.mfa.clone().filter(|m| m.enabled);
+    match mfa.and_then(|m| m.verify(code, now)) {
+        Some(mfa) => {
+            redis_cli.login_succeeded(&email).await?;
+            Ok(Ok(mfa))
+        }
+        None => {
+            if redis_cli.login_failed(&email, ip.as_deref()).await? {
+                lock_notify(user.email.clone());
+            }
+            Ok(Err(Responser::new(Some("验证码错误"), failed).to_result()?))
+        }
+    }
+}
+
+// 帐号被锁定时通知本人，后台发送，不影响登录接口的返回和响应时间
+fn lock_notify(email: String) {
+    let message = format!(
+        "Your account has been locked for {} minutes after too many failed login attempts.",
+        *LOGIN_LOCK_TIME / 60
+    );
+    task::spawn(async move {
+        if let Err(e) = send_email(&email, "Account Locked API TEST Email", &message).await {
+            log::error!("Send lock email error: {}", e);
+        }
+    });
+}
+
+// 邮件登录链接，帐号不存在时也返回成功，避免泄露注册信息
+pub(crate) async fn magic(mut req: Request<State>) -> tide::Result {
+    let mut data: MagicLink = req.body_json().await?;
+    data.email = data.email.to_lowercase();
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let mut opt = Options::default();
+    opt.find_one_opt(&USER, Some(doc! { "email": &data.email }));
+    if let Some(user_doc) = req.state().mongo.find(opt).await?.pop() {
+        let id = user_doc.get_object_id("_id")?.to_hex();
+        let redis_cli = &req.state().redis;
+        let token = redis_cli.set_once_token("magic", &id, *MAGIC_EXPIRE_TIME).await?;
+        let link = format!("{}/api/v1/auth/magic/confirm/{}", CONFIG.server.domain, token);
+        // 后台发送，帐号存在与否响应时间一致
+        task::spawn(async move {
+            if let Err(e) = send_email(&data.email, "Login API TEST Email", &link).await {
+                log::error!("Send magic link email error: {}", e);
+            }
+        });
+    }
+    Responser::new(Some("登录邮件已发送，请查收"), &status::OK).to_result()
+}
+
+// 用邮件中的 token 换取 session，能收到邮件说明邮箱有效，未激活的帐号一并激活
+pub(crate) async fn magic_confirm(mut req: Request<State>) -> tide::Result {
+    let token: Token = req.body_json().await?;
+    let redis_cli = req.state().redis.clone();
+    let mongo_col = &req.state().mongo;
+
+    let id = match redis_cli.take_once_token("magic", &token.token).await? {
+        Some(id) => id,
+        None => return Responser::new(Some("链接已失效"), &status::BAD_REQUEST).to_result(),
+    };
+    let user = match find_user(mongo_col, &id).await? {
+        Some(user) => user,
         None => return Responser::new(Some("帐号不存在"), &status::BAD_REQUEST).to_result(),
     };
+    activate_verified(mongo_col, &redis_cli, &id, &user).await?;
+    issue_session(&req, id, &user).await
+}
 
-    let id = data.get_object_id("_id")?.to_hex();
-    let user: User = from_document(data)?;
-
-    // 密码检测
-    return if password_verify(&user.password, &req_data.password) {
-        // Session 生成
-        let token_str: String = redis_con.get(&id).await?;
-        if !token_str.is_empty() {
-            // session时间小于1小时重新生成
-            let seconds: usize = redis_con.ttl(&id).await?;
-            if seconds < 3600 {
-                redis_con.del(&id).await?;
-                redis_con.del(&token_str).await?;
-                let token = redis_cli.set_token(id).await?;
-                return Responser::new(Some(token), &status::OK).to_result();
+pub(crate) async fn login_mfa(mut req: Request<State>) -> tide::Result {
+    let data: LoginMfa = req.body_json().await?;
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let redis_cli = req.state().redis.clone();
+    let mongo_col = &req.state().mongo;
+
+    let id = match redis_cli.peek_once_token("mfa", &data.mfa_token).await? {
+        Some(id) => id,
+        None => return Responser::new(Some("登录已过期，请重新登录"), &status::UNAUTH).to_result(),
+    };
+    let filter = doc! { "_id": ObjectId::with_string(&id)? };
+    let mut opt = Options::default();
+    opt.find_one_opt(&USER, Some(filter.clone()));
+    let user: User = match mongo_col.find(opt).await?.pop() {
+        Some(d) => from_document(d)?,
+        None => return Responser::new(Some("帐号不存在"), &status::UNAUTH).to_result(),
+    };
+    let mfa = match verify_mfa(&req, &user, &data.code, &status::UNAUTH).await? {
+        Ok(mfa) => mfa,
+        Err(res) => {
+            redis_cli
+                .fail_once_token("mfa", &data.mfa_token, MFA_MAX_ATTEMPTS)
+                .await?;
+            return Ok(res);
+        }
+    };
+    // 并发提交时只有一个请求能取到 token
+    if redis_cli.take_once_token("mfa", &data.mfa_token).await?.is_none() {
+        return Responser::new(Some("登录已过期，请重新登录"), &status::UNAUTH).to_result();
+    }
+
+    let mut opt = Options::default();
+    opt.update_opt(&USER, Some(filter), None);
+    mongo_col
+        .update(doc! { "$set": { "mfa": to_bson(&mfa)? } }, opt)
+        .await?;
+    let token = redis_cli
+        .set_token(id, &user.roles, new_session(&req))
+        .await?;
+    Responser::new(Some(token), &status::OK).to_result()
+}
+
+// 第三方登录第一步，返回身份提供方的授权地址
+pub(crate) async fn oidc_login(req: Request<State>) -> tide::Result {
+    let name = req.param::<String>("provider")?;
+    let provider = match CONFIG.oidc_provider(&name) {
+        Some(p) => p,
+        None => return Responser::new(Some("不支持的登录方式"), &status::BAD_REQUEST).to_result(),
+    };
+    // state 对应 provider:verifier，回调时取出 verifier 换取 token
+    let verifier = rand_str(64);
+    let value = format!("{}:{}", provider.name, verifier);
+    let redis_cli = &req.state().redis;
+    let state = redis_cli.set_once_token("oidc", &value, *OIDC_EXPIRE_TIME).await?;
+    let url = match oidc::authorize_url(provider, &state, &verifier) {
+        Ok(url) => url,
+        Err(e) => return Responser::new(Some(e), &status::SYS_ERROR).to_result(),
+    };
+    // state 同时写入 cookie，回调时必须来自同一个浏览器，防止登录 CSRF
+    let cookie = Cookie::build(OIDC_STATE_COOKIE, state.clone())
+        .path("/api/v1/auth/oidc")
+        .http_only(true)
+        .secure(CONFIG.server.domain.starts_with("https"))
+        .same_site(SameSite::Lax)
+        .finish();
+    let mut res = Responser::new(Some(OidcRedirect { url, state }), &status::OK).to_result()?;
+    res.insert_cookie(cookie);
+    Ok(res)
+}
+
+// 身份提供方回调后由前端提交 code 和 state
+pub(crate) async fn oidc_callback(mut req: Request<State>) -> tide::Result {
+    let data: OidcCallback = req.body_json().await?;
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let name = req.param::<String>("provider")?;
+    let provider = match CONFIG.oidc_provider(&name) {
+        Some(p) => p,
+        None => return Responser::new(Some("不支持的登录方式"), &status::BAD_REQUEST).to_result(),
+    };
+    let bound = req.cookie(OIDC_STATE_COOKIE);
+    if bound.map(|c| c.value() != data.state).unwrap_or(true) {
+        return Responser::new(Some("登录已过期"), &status::BAD_REQUEST).to_result();
+    }
+    let redis_cli = req.state().redis.clone();
+    let verifier = match redis_cli.take_once_token("oidc", &data.state).await? {
+        Some(value) => match value.rsplit_once(':') {
+            Some((p, verifier)) if p == provider.name => verifier.to_string(),
+            _ => return Responser::new(Some("登录已过期"), &status::BAD_REQUEST).to_result(),
+        },
+        None => return Responser::new(Some("登录已过期"), &status::BAD_REQUEST).to_result(),
+    };
+    let claims = match oidc::exchange(provider, &data.code, &verifier).await {
+        Ok(claims) => claims,
+        Err(e) => {
+            log::error!("OIDC {} exchange error: {}", provider.name, e);
+            return Responser::new(Some("第三方登录失败"), &status::UNAUTH).to_result();
+        }
+    };
+
+    let mongo_col = &req.state().mongo;
+    let mut opt = Options::default();
+    let filter = doc! { "provider": &provider.name, "subject": &claims.sub };
+    opt.find_one_opt(&IDENTITY, Some(filter));
+    let linked = match mongo_col.find(opt).await?.pop() {
+        Some(d) => Some(from_document::<Identity>(d)?.user_id),
+        None => None,
+    };
+    let id = match linked {
+        Some(id) => id,
+        None => {
+            let email = match claims.verified_email() {
+                Some(email) => email,
+                None => {
+                    return Responser::new(Some("第三方帐号邮箱未验证"), &status::UNAUTH)
+                        .to_result()
+                }
+            };
+            let id = match_or_provision(&req, &email, claims.username(&email)).await?;
+            let identity = Identity {
+                provider: provider.name.clone(),
+                subject: claims.sub.clone(),
+                user_id: id.clone(),
+                email: Some(email),
+                create_at: Local::now(),
+            };
+            let mut opt = Options::default();
+            opt.set_collect(&IDENTITY);
+            // 同一个第三方帐号并发回调时由唯一索引拦截，沿用已经绑定的帐号
+            match mongo_col.insert_one(opt, &identity).await {
+                Ok(_) => id,
+                Err(e) if is_duplicate(&e) => {
+                    let mut opt = Options::default();
+                    let filter = doc! { "provider": &provider.name, "subject": &claims.sub };
+                    opt.find_one_opt(&IDENTITY, Some(filter));
+                    match mongo_col.find(opt).await?.pop() {
+                        Some(d) => from_document::<Identity>(d)?.user_id,
+                        None => return Err(e),
+                    }
+                }
+                Err(e) => return Err(e),
             }
-            let token = Token { token: token_str };
-            Responser::new(Some(token), &status::OK).to_result()
-        } else {
-            let token = redis_cli.set_token(id).await?;
-            Responser::new(Some(token), &status::OK).to_result()
         }
-    } else {
-        Responser::new(Some(""), &status::UNAUTH).to_result()
     };
+
+    let mut opt = Options::default();
+    opt.find_one_opt(&USER, Some(doc! { "_id": ObjectId::with_string(&id)? }));
+    let user: User = match mongo_col.find(opt).await?.pop() {
+        Some(d) => from_document(d)?,
+        None => return Responser::new(Some("帐号不存在"), &status::UNAUTH).to_result(),
+    };
+    issue_session(&req, id, &user).await
+}
+
+// 按已验证的邮箱匹配帐号，未激活的帐号直接激活，不存在时自动创建
+async fn match_or_provision(
+    req: &Request<State>,
+    email: &str,
+    username: String,
+) -> tide::Result<String> {
+    if let Some(id) = match_verified(req, email).await? {
+        return Ok(id);
+    }
+
+    let user = User::provisioned(username, email.to_string())?;
+    let mut opt = Options::default();
+    opt.set_collect(&USER);
+    // 同一邮箱并发回调或同时注册时由唯一索引拦截，改为匹配已有帐号
+    match req.state().mongo.insert_one(opt, &user).await {
+        Ok(id) => Ok(id),
+        Err(e) if is_duplicate(&e) => match match_verified(req, email).await? {
+            Some(id) => Ok(id),
+            None => Err(e),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+async fn match_verified(req: &Request<State>, email: &str) -> tide::Result<Option<String>> {
+    let mongo_col = &req.state().mongo;
+    let mut opt = Options::default();
+    opt.find_one_opt(&USER, Some(doc! { "email": email }));
+    let d = match mongo_col.find(opt).await?.pop() {
+        Some(d) => d,
+        None => return Ok(None),
+    };
+    let id = d.get_object_id("_id")?.to_hex();
+    let user: User = from_document(d)?;
+    activate_verified(mongo_col, &req.state().redis, &id, &user).await?;
+    Ok(Some(id))
+}
+
+pub(crate) async fn refresh(mut req: Request<State>) -> tide::Result {
+    let data: Refresh = req.body_json().await?;
+    match req.state().redis.refresh_token(&data.refresh_token).await? {
+        Some(token) => Responser::new(Some(token), &status::OK).to_result(),
+        None => {
+            Responser::new(Some("refresh token 已失效，请重新登录"), &status::UNAUTH).to_result()
+        }
+    }
+}
+
+pub(crate) async fn logout(req: Request<State>) -> tide::Result {
+    let token = match bearer(&req) {
+        Some(token) => token,
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    if !req.state().redis.logout(&token).await? {
+        return Responser::new(Some(""), &status::UNAUTH).to_result();
+    }
+    Responser::new(Some("success"), &status::OK).to_result()
 }
 
 pub(crate) async fn register(mut req: Request<State>) -> tide::Result {
-    let reg = req.body_json::<Register>().await?;
+    let mut reg = req.body_json::<Register>().await?;
+    // 邮箱统一按小写保存和查询
+    reg.email = reg.email.to_lowercase();
     if let Err(e) = reg.validate() {
         return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
     }
+    if let Err(e) = password::validate(&reg.password, &[&reg.username, &reg.email]) {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
     let mongo_col = &req.state().mongo;
     let redis_cli = req.state().redis.clone();
 
@@ -75,51 +473,62 @@ pub(crate) async fn register(mut req: Request<State>) -> tide::Result {
     }
 
     // 写入数据库
-    let user = User::from(reg);
+    let user = User::try_from(reg)?;
     let mut opt = Options::default();
     opt.set_collect(&USER);
-    let id = mongo_col.insert_one(opt, &user).await?;
-
-    let token = redis_cli.set_token(id).await?;
-    send_email(
-        &email,
-        "Register API TEST Email",
-        &format!(
-            "{}/api/v1/auth/confirm/{}",
-            CONFIG.server.domain, token.token
-        ),
-    )
-    .await?;
+    // 并发注册时由唯一索引拦截
+    let id = match mongo_col.insert_one(opt, &user).await {
+        Ok(id) => id,
+        Err(e) if is_duplicate(&e) => {
+            return Responser::new(Some("帐号已注册"), &status::BAD_REQUEST).to_result()
+        }
+        Err(e) => return Err(e),
+    };
+    record_password(mongo_col, &id, user.password).await?;
 
+    let token = redis_cli.set_once_token("confirm", &id, *CONFIRM_EXPIRE_TIME).await?;
+    let link = format!("{}/api/v1/auth/confirm/{}", CONFIG.server.domain, token);
+    // 后台发送，帐号已经写入，发送失败时可以重新发送
+    task::spawn(async move {
+        if let Err(e) = send_email(&email, "Register API TEST Email", &link).await {
+            log::error!("Send register email error: {}", e);
+        }
+    });
     Responser::new(Some("success"), &status::OK).to_result()
 }
 
 pub(crate) async fn confirm(mut req: Request<State>) -> tide::Result {
     let token: Token = req.body_json().await?;
     let redis_cli = req.state().redis.clone();
-    let mut redis_con = redis_cli.connection().await?;
     let mongo_col = &req.state().mongo;
 
-    let id_str: String = redis_con.get(&token.token).await?;
+    let id_str = match redis_cli.take_once_token("confirm", &token.token).await? {
+        Some(id) => id,
+        None => return Responser::new(Some("链接已失效"), &status::BAD_REQUEST).to_result(),
+    };
     let filter = doc! { "_id": ObjectId::with_string(&id_str)? };
     let data = doc! { "$set": { "active": true } };
     let mut opt = Options::default();
-
-    redis_con.del(&token.token).await?;
-    redis_con.del(&id_str).await?;
-
-    opt.update_opt(&USER, Some(filter), None);
+    opt.update_opt(&USER, Some(filter.clone()), None);
     mongo_col.update(data, opt).await?;
 
-    let token = redis_cli.set_token(id_str).await?;
-
-    Responser::new(Some(token), &status::OK).to_result()
+    // 激活后直接登录，开启两步验证时同样需要验证码
+    let mut opt = Options::default();
+    opt.find_one_opt(&USER, Some(filter));
+    let user: User = match mongo_col.find(opt).await?.pop() {
+        Some(d) => from_document(d)?,
+        None => return Responser::new(Some("帐号不存在"), &status::BAD_REQUEST).to_result(),
+    };
+    issue_session(&req, id_str, &user).await
 }
 
 pub(crate) async fn resend(mut req: Request<State>) -> tide::Result {
-    let data: Resend = req.body_json().await?;
+    let mut data: Resend = req.body_json().await?;
+    data.email = data.email.to_lowercase();
+    if data.forget == Some(true) {
+        return forget(req, data).await;
+    }
     let redis_cli = &req.state().redis;
-    let mut redis_con = redis_cli.connection().await?;
     let mongo_col = &req.state().mongo;
 
     let email = data.email.clone();
@@ -133,40 +542,78 @@ pub(crate) async fn resend(mut req: Request<State>) -> tide::Result {
     }
     let data = user_doc[0].clone();
     let id = data.get_object_id("_id")?.to_hex();
+    if from_document::<User>(data)?.active {
+        return Responser::new(Some("帐号已激活"), &status::BAD_REQUEST).to_result();
+    }
 
-    let token_str: String = redis_con.get(&id).await?;
-
-    if !token_str.is_empty() {
-        // session时间小于1小时重新生成
-        let seeds: usize = redis_con.ttl(&id).await?;
-        if seeds > 3600 {
-            return Responser::new(Some("请勿重复发送"), &status::BAD_REQUEST).to_result();
-        }
-        redis_con.del(&id).await?;
-        redis_con.del(&token_str).await?;
-        let token = redis_cli.set_token(id.clone()).await?;
-        send_email(
-            &email,
-            "Resend API TEST Email",
-            &format!(
-                "{}/api/v1/auth/confirm/{}",
-                CONFIG.server.domain, token.token
-            ),
-        )
-        .await?;
-        return Responser::new(Some(token), &status::OK).to_result();
-    }
-    let token = redis_cli.set_token(id).await?;
-    send_email(
-        &email,
-        "Resend API TEST Email",
-        &format!(
-            "{}/api/v1/auth/confirm/{}",
-            CONFIG.server.domain, token.token
-        ),
-    )
-    .await?;
-    Responser::new(Some(token), &status::OK).to_result()
+    // 一分钟内不重复发送
+    if !redis_cli.email_cooldown("resend", &id).await? {
+        return Responser::new(Some("请勿重复发送"), &status::BAD_REQUEST).to_result();
+    }
+    let token = redis_cli.set_once_token("confirm", &id, *CONFIRM_EXPIRE_TIME).await?;
+    let link = format!("{}/api/v1/auth/confirm/{}", CONFIG.server.domain, token);
+    task::spawn(async move {
+        if let Err(e) = send_email(&email, "Resend API TEST Email", &link).await {
+            log::error!("Send confirm email error: {}", e);
+        }
+    });
+    Responser::new(Some("success"), &status::OK).to_result()
+}
+
+// 忘记密码，发送重置邮件。帐号不存在时也返回成功，避免泄露注册信息
+async fn forget(req: Request<State>, data: Resend) -> tide::Result {
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let mut opt = Options::default();
+    opt.find_one_opt(&USER, Some(doc! { "email": &data.email }));
+    let user_doc = req.state().mongo.find(opt).await?.pop();
+    if let Some(user_doc) = user_doc {
+        let id = user_doc.get_object_id("_id")?.to_hex();
+        let redis_cli = &req.state().redis;
+        // 一分钟内不重复发送，但响应不变
+        if redis_cli.email_cooldown("forget", &id).await? {
+            let token = redis_cli.set_once_token("reset", &id, *RESET_EXPIRE_TIME).await?;
+            let link = format!("{}/api/v1/auth/resetpwd/confirm/{}", CONFIG.server.domain, token);
+            // 后台发送，帐号存在与否响应时间一致
+            task::spawn(async move {
+                let subject = "Reset Password API TEST Email";
+                if let Err(e) = send_email(&data.email, subject, &link).await {
+                    log::error!("Send reset password email error: {}", e);
+                }
+            });
+        }
+    }
+    Responser::new(Some("重置邮件已发送，请查收"), &status::OK).to_result()
+}
+
+pub(crate) async fn reset_pwd_confirm(mut req: Request<State>) -> tide::Result {
+    let data: ResetPwdConfirm = req.body_json().await?;
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let redis_cli = &req.state().redis;
+    let mongo_col = &req.state().mongo;
+    // 密码不符合要求时链接仍然有效
+    let id = match redis_cli.peek_once_token("reset", &data.token).await? {
+        Some(id) => id,
+        None => return Responser::new(Some("链接已失效"), &status::BAD_REQUEST).to_result(),
+    };
+    let user = match find_user(mongo_col, &id).await? {
+        Some(user) => user,
+        None => return Responser::new(Some("帐号不存在"), &status::BAD_REQUEST).to_result(),
+    };
+    if let Err(e) = check_new_password(mongo_col, &id, &user, &data.password).await? {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    if redis_cli.take_once_token("reset", &data.token).await?.is_none() {
+        return Responser::new(Some("链接已失效"), &status::BAD_REQUEST).to_result();
+    }
+    save_password(mongo_col, &id, &data.password).await?;
+
+    // 重置成功后所有已登录的 session 失效
+    redis_cli.clear_tokens(&id).await?;
+    Responser::new(Some("密码修改成功！"), &status::OK).to_result()
 }
 
 pub async fn reset_pwd(mut req: Request<State>) -> tide::Result {
@@ -174,24 +621,204 @@ pub async fn reset_pwd(mut req: Request<State>) -> tide::Result {
     if let Err(e) = pwd_data.validate() {
         return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
     }
-    let token = match req.ext::<Token>() {
+    let id = match session_owner(&req) {
         None => {
             return Responser::new(Some("请先登录，或者发送验证邮件！"), &status::UNAUTH)
                 .to_result()
         }
-        Some(t) => t,
+        Some(id) => id,
     };
 
+    let mongo_col = &req.state().mongo;
+    let user = match find_user(mongo_col, &id).await? {
+        Some(user) => user,
+        None => return Responser::new(Some("帐号不存在"), &status::BAD_REQUEST).to_result(),
+    };
+    if !password_verify(&user.password, &pwd_data.old_password) {
+        return Responser::new(Some("密码错误"), &status::BAD_REQUEST).to_result();
+    }
+    if let Err(e) = check_new_password(mongo_col, &id, &user, &pwd_data.password).await? {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    save_password(mongo_col, &id, &pwd_data.password).await?;
+
+    // 除当前 session 外全部失效
     let redis_cli = &req.state().redis;
-    let mut redis_con = redis_cli.connection().await?;
+    match bearer(&req) {
+        Some(access) => redis_cli.clear_other_tokens(&id, &access).await?,
+        None => redis_cli.clear_tokens(&id).await?,
+    }
+    return Responser::new(Some("密码修改成功！"), &status::OK).to_result();
+}
+
+// 新邮箱确认后切换，同一邮箱被多个帐号申请时先确认的生效
+pub(crate) async fn email_confirm(mut req: Request<State>) -> tide::Result {
+    let token: Token = req.body_json().await?;
+    let redis_cli = req.state().redis.clone();
     let mongo_col = &req.state().mongo;
-    let id: String = redis_con.get(&token.token).await?;
 
+    let id = match redis_cli.take_once_token("email", &token.token).await? {
+        Some(id) => id,
+        None => return Responser::new(Some("链接已失效"), &status::BAD_REQUEST).to_result(),
+    };
+    let change = match find_user(mongo_col, &id).await?.and_then(|u| u.email_change) {
+        Some(change) if change.confirm_at.is_none() => change,
+        _ => return Responser::new(Some("链接已失效"), &status::BAD_REQUEST).to_result(),
+    };
+
+    let oid = ObjectId::with_string(&id)?;
     let mut opt = Options::default();
-    let filter = doc! { "_id": ObjectId::with_string(&id)?};
+    let filter = doc! { "_id": oid.clone(), "email": &change.old_email };
     opt.update_opt(&USER, Some(filter), None);
-    let password = hash_password(&pwd_data.password);
-    let data = doc! { "$set": { "password": password }};
-    let _ = mongo_col.update(data, opt).await?;
-    return Responser::new(Some("密码修改成功！"), &status::OK).to_result();
+    let update = doc! { "$set": {
+        "email": &change.new_email,
+        "email_change.confirm_at": to_bson(&Local::now())?,
+    } };
+    match mongo_col.update(update, opt).await {
+        Ok(0) => return Responser::new(Some("链接已失效"), &status::BAD_REQUEST).to_result(),
+        Ok(_) => {}
+        Err(e) if is_duplicate(&e) => {
+            let mut opt = Options::default();
+            opt.update_opt(&USER, Some(doc! { "_id": oid }), None);
+            mongo_col.update(doc! { "$unset": { "email_change": "" } }, opt).await?;
+            return Responser::new(Some("邮箱已被使用"), &status::BAD_REQUEST).to_result();
+        }
+        Err(e) => return Err(e),
+    }
+    Responser::new(Some("邮箱修改成功！"), &status::OK).to_result()
+}
+
+// 旧邮箱撤销修改，已经切换的恢复为旧邮箱，并让所有 session 失效
+pub(crate) async fn email_cancel(mut req: Request<State>) -> tide::Result {
+    let token: Token = req.body_json().await?;
+    let redis_cli = req.state().redis.clone();
+    let mongo_col = &req.state().mongo;
+
+    let id = match redis_cli.take_once_token("email_cancel", &token.token).await? {
+        Some(id) => id,
+        None => return Responser::new(Some("链接已失效"), &status::BAD_REQUEST).to_result(),
+    };
+    let change = match find_user(mongo_col, &id).await?.and_then(|u| u.email_change) {
+        Some(change) => change,
+        None => return Responser::new(Some("链接已失效"), &status::BAD_REQUEST).to_result(),
+    };
+
+    let oid = ObjectId::with_string(&id)?;
+    let mut opt = Options::default();
+    if change.confirm_at.is_none() {
+        opt.update_opt(&USER, Some(doc! { "_id": oid }), None);
+        mongo_col.update(doc! { "$unset": { "email_change": "" } }, opt).await?;
+        return Responser::new(Some("已撤销邮箱修改"), &status::OK).to_result();
+    }
+    opt.update_opt(&USER, Some(doc! { "_id": oid, "email": &change.new_email }), None);
+    let update = doc! {
+        "$set": { "email": &change.old_email },
+        "$unset": { "email_change": "" },
+    };
+    match mongo_col.update(update, opt).await {
+        Ok(0) => return Responser::new(Some("链接已失效"), &status::BAD_REQUEST).to_result(),
+        Ok(_) => {}
+        Err(e) if is_duplicate(&e) => {
+            let msg = "原邮箱已被其他帐号使用";
+            return Responser::new(Some(msg), &status::BAD_REQUEST).to_result();
+        }
+        Err(e) => return Err(e),
+    }
+    redis_cli.clear_tokens(&id).await?;
+    Responser::new(Some("已撤销邮箱修改"), &status::OK).to_result()
+}
+
+// 按密码策略校验新密码，并且不能与当前及最近用过的密码相同
+async fn check_new_password(
+    mongo: &MongoDb,
+    id: &str,
+    user: &User,
+    new_password: &str,
+) -> tide::Result<Result<(), ValidationErrors>> {
+    if let Err(e) = password::validate(new_password, &[&user.username, &user.email]) {
+        return Ok(Err(e));
+    }
+    let history = CONFIG.password.history;
+    if history == 0 {
+        return Ok(Ok(()));
+    }
+    let sort = doc! { "create_at": -1 };
+    let filter = doc! { "user_id": id };
+    let limit = Some(history as i64);
+    let opt = Options::new(&PASSWORD_HISTORY, Some(filter), limit, None, Some(sort), None);
+    let mut hashes = vec![user.password.clone()];
+    for data in mongo.find(opt).await? {
+        hashes.push(from_document::<PasswordHistory>(data)?.hash);
+    }
+    if hashes.iter().any(|h| password_verify(h, new_password)) {
+        return Ok(Err(password::reused()));
+    }
+    Ok(Ok(()))
+}
+
+async fn save_password(mongo: &MongoDb, id: &str, new_password: &str) -> tide::Result<()> {
+    let hash = hash_password(new_password)?;
+    let mut opt = Options::default();
+    opt.update_opt(&USER, Some(doc! { "_id": ObjectId::with_string(id)? }), None);
+    mongo.update(doc! { "$set": { "password": &hash } }, opt).await?;
+    record_password(mongo, id, hash).await
+}
+
+// 记录密码 hash，只保留最近 history 条
+async fn record_password(mongo: &MongoDb, id: &str, hash: String) -> tide::Result<()> {
+    let history = CONFIG.password.history;
+    if history == 0 {
+        return Ok(());
+    }
+    let record = PasswordHistory {
+        user_id: id.to_string(),
+        hash,
+        create_at: Local::now(),
+    };
+    let mut opt = Options::default();
+    opt.set_collect(&PASSWORD_HISTORY);
+    mongo.insert_one(opt, &record).await?;
+
+    let sort = doc! { "create_at": -1 };
+    let fields = doc! { "_id": 1 };
+    let filter = doc! { "user_id": id };
+    let skip = Some(history as i64);
+    let opt = Options::new(&PASSWORD_HISTORY, Some(filter), None, skip, Some(sort), Some(fields));
+    let old: Vec<Bson> = mongo
+        .find(opt)
+        .await?
+        .iter()
+        .filter_map(|d| d.get("_id").cloned())
+        .collect();
+    if !old.is_empty() {
+        let mut opt = Options::default();
+        opt.del_opt(&PASSWORD_HISTORY, Some(doc! { "_id": { "$in": old } }), Some(0));
+        mongo.delete(opt).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::forwarded_ip;
+
+    #[test]
+    fn test_forwarded_ip() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        let proxy = ip("10.0.0.1");
+        let trusted = vec![proxy, ip("10.0.0.2")];
+
+        // 不经过受信任的代理时忽略 X-Forwarded-For
+        assert_eq!(forwarded_ip(ip("1.2.3.4"), "5.6.7.8", &trusted), ip("1.2.3.4"));
+        assert_eq!(forwarded_ip(proxy, "", &trusted), proxy);
+        assert_eq!(forwarded_ip(proxy, "5.6.7.8", &trusted), ip("5.6.7.8"));
+        // 客户端自己加的 X-Forwarded-For 在最左边，不会被采用
+        assert_eq!(
+            forwarded_ip(proxy, "9.9.9.9, 5.6.7.8, 10.0.0.2", &trusted),
+            ip("5.6.7.8")
+        );
+        assert_eq!(forwarded_ip(proxy, "bad, 10.0.0.2", &trusted), ip("10.0.0.2"));
+    }
 }
@@ -8,6 +8,48 @@ pub(crate) struct Login {
     pub(crate) password: String,
 }
 
+#[derive(Deserialize, Validate)]
+pub(crate) struct MagicLink {
+    #[validate(email(message = "email type error"))]
+    pub(crate) email: String,
+}
+
+// 开启两步验证的帐号，密码正确后返回该结构，凭 mfa_token 和验证码完成登录
+#[derive(Serialize)]
+pub(crate) struct MfaPending {
+    pub(crate) mfa_token: String,
+    pub(crate) expires_in: usize,
+}
+
+#[derive(Deserialize, Validate)]
+pub(crate) struct LoginMfa {
+    #[validate(length(min = 1, message = "mfa_token can not be empty"))]
+    pub(crate) mfa_token: String,
+    // TOTP 验证码或恢复码
+    #[validate(length(min = 1, message = "code can not be empty"))]
+    pub(crate) code: String,
+}
+
+// 第三方登录的跳转地址，state 用于回调时校验
+#[derive(Serialize)]
+pub(crate) struct OidcRedirect {
+    pub(crate) url: String,
+    pub(crate) state: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub(crate) struct OidcCallback {
+    #[validate(length(min = 1, message = "code can not be empty"))]
+    pub(crate) code: String,
+    #[validate(length(min = 1, message = "state can not be empty"))]
+    pub(crate) state: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Refresh {
+    pub(crate) refresh_token: String,
+}
+
 #[derive(Deserialize, Validate)]
 pub(crate) struct Register {
     #[validate(length(min = 4, message = "username min length 4"))]
@@ -15,10 +57,8 @@ pub(crate) struct Register {
     #[validate(email(message = "email type error"))]
     pub(crate) email: String,
     pub(crate) phone: String,
-    #[validate(
-        must_match = "confirm",
-        length(min = 6, message = "password length min 6")
-    )]
+    // 长度、字符类型等由 utils::password 按配置校验
+    #[validate(must_match = "confirm")]
     pub(crate) password: String,
     pub(crate) confirm: String,
 }
@@ -30,12 +70,21 @@ pub(crate) struct Resend {
     pub(crate) forget: Option<bool>,
 }
 
+#[derive(Deserialize, Validate)]
+pub(crate) struct ResetPwdConfirm {
+    #[validate(length(min = 1, message = "token can not be empty"))]
+    pub(crate) token: String,
+    #[validate(must_match = "confirm")]
+    pub(crate) password: String,
+    pub(crate) confirm: String,
+}
+
 #[derive(Deserialize, Validate)]
 pub(crate) struct ResetPwd {
-    #[validate(
-        must_match = "confirm",
-        length(min = 6, message = "password length min 6")
-    )]
+    // 登录状态下修改密码需要验证当前密码
+    #[validate(length(min = 1, message = "old password can not be empty"))]
+    pub(crate) old_password: String,
+    #[validate(must_match = "confirm")]
     pub(crate) password: String,
     pub(crate) confirm: String,
 }
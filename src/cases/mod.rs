@@ -0,0 +1,22 @@
+mod routers;
+mod schema;
+
+use tide::Server;
+
+use crate::State;
+use routers::{add_case, delete_case, get_case, get_run, list_case, list_run, run_case, update_case};
+use crate::middleware::{guard, LoginMiddleware, ProjectMiddleware};
+
+pub(crate) fn case_router(app: &mut Server<State>) {
+    let mut case = app.at("/project/:pid/case");
+    case.with(LoginMiddleware).with(ProjectMiddleware);
+    case.at("/").get(guard("case:read", list_case));
+    case.at("/add").post(guard("case:write", add_case));
+    case.at("/:id")
+        .get(guard("case:read", get_case))
+        .put(guard("case:write", update_case))
+        .delete(guard("case:write", delete_case));
+    case.at("/:id/run").post(guard("case:run", run_case));
+    case.at("/:id/runs").get(guard("case:read", list_run));
+    case.at("/run/:id").get(guard("case:read", get_run));
+}
@@ -0,0 +1,361 @@
+use chrono::prelude::Local;
+use mongodb::bson::{doc, from_document, oid::ObjectId, to_bson, Document};
+use tide::Request;
+use validator::Validate;
+
+use super::schema::{AddCase, GetCase, GetRun, ResCase, ResRun, RunCase};
+use crate::db::{MongoDb, Options};
+use crate::middleware::CurrentUser;
+use crate::models::{
+    Case, Environment, Interface, Run, Step, CASE, ENVIRONMENT, INTERFACE, RUN, STEP,
+};
+use crate::runner::{mask_secrets, Planned, Runner};
+use crate::utils::*;
+use crate::State;
+
+fn to_res_case(data: Document) -> Option<ResCase> {
+    let id = data.get_object_id("_id").ok()?.to_hex();
+    let case = from_document::<Case>(data).ok()?;
+    Some(ResCase { id, case })
+}
+
+fn to_res_run(data: Document) -> Option<ResRun> {
+    let id = data.get_object_id("_id").ok()?.to_hex();
+    let run = from_document::<Run>(data).ok()?;
+    Some(ResRun { id, run })
+}
+
+// 返回不存在或不属于本项目的步骤 id
+async fn missing_steps(mongo: &MongoDb, case: &Case) -> tide::Result<Vec<String>> {
+    let mut ids = case.step_ids();
+    ids.sort();
+    ids.dedup();
+    let mut oids = Vec::new();
+    let mut missing = Vec::new();
+    for id in ids {
+        match ObjectId::with_string(id) {
+            Ok(oid) => oids.push(oid),
+            Err(_) => missing.push(id.clone()),
+        }
+    }
+
+    let mut opt = Options::default();
+    opt.set_collect(&STEP);
+    opt.filter = Some(doc! { "_id": { "$in": oids.clone() }, "project_id": &case.project_id });
+    opt.fileds = Some(doc! { "_id": 1 });
+    let found = mongo
+        .find(opt)
+        .await?
+        .into_iter()
+        .filter_map(|d| d.get_object_id("_id").ok().cloned())
+        .collect::<Vec<ObjectId>>();
+    for oid in oids {
+        if !found.contains(&oid) {
+            missing.push(oid.to_hex());
+        }
+    }
+    Ok(missing)
+}
+
+pub(crate) async fn add_case(mut req: Request<State>) -> tide::Result {
+    let data: AddCase = match req.body_json().await {
+        Ok(data) => data,
+        Err(error) => {
+            return Responser::new(Some(format!("{}", error)), &status::BAD_REQUEST).to_result()
+        }
+    };
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+
+    let case = data.into_case(req.param::<String>("pid")?, user_id);
+    let mongo = &req.state().mongo;
+    let missing = missing_steps(mongo, &case).await?;
+    if !missing.is_empty() {
+        return Responser::new(Some(missing), &status::BAD_REQUEST).to_result();
+    }
+    let mut opt = Options::default();
+    opt.set_collect(&CASE);
+    let id = mongo.insert_one(opt, &case).await?;
+    Responser::new(Some(ResCase { id, case }), &status::OK).to_result()
+}
+
+pub(crate) async fn get_case(req: Request<State>) -> tide::Result {
+    crud::get_one(&req, &CASE, to_res_case, "用例不存在").await
+}
+
+pub(crate) async fn list_case(req: Request<State>) -> tide::Result {
+    let filter: GetCase = match req.query() {
+        Ok(res) => res,
+        Err(e) => return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result(),
+    };
+
+    let mut condition = doc! { "project_id": req.param::<String>("pid")? };
+    if let Some(name) = filter.name {
+        condition.insert("name", name);
+    }
+    if let Some(tag) = filter.tag {
+        condition.insert("tags", tag);
+    }
+
+    crud::list(&req, &CASE, condition, &filter.page, to_res_case).await
+}
+
+pub(crate) async fn update_case(mut req: Request<State>) -> tide::Result {
+    let data: AddCase = match req.body_json().await {
+        Ok(data) => data,
+        Err(error) => {
+            return Responser::new(Some(format!("{}", error)), &status::BAD_REQUEST).to_result()
+        }
+    };
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let case = data.into_case(req.param::<String>("pid")?, user_id);
+    let missing = missing_steps(&req.state().mongo, &case).await?;
+    if !missing.is_empty() {
+        return Responser::new(Some(missing), &status::BAD_REQUEST).to_result();
+    }
+
+    let set = doc! {
+        "name": &case.name,
+        "description": &case.description,
+        "tags": to_bson(&case.tags)?,
+        "setup": to_bson(&case.setup)?,
+        "steps": to_bson(&case.steps)?,
+        "teardown": to_bson(&case.teardown)?,
+    };
+    crud::update_one(&req, &CASE, set, "用例不存在").await
+}
+
+pub(crate) async fn delete_case(req: Request<State>) -> tide::Result {
+    crud::delete_one(&req, &CASE, "用例不存在").await
+}
+
+// 按 id 查出项目中的文档，返回 hex id -> 文档
+async fn find_by_ids(
+    mongo: &MongoDb,
+    collect: &str,
+    project_id: &str,
+    ids: &[&String],
+) -> tide::Result<Vec<(String, Document)>> {
+    let oids = ids
+        .iter()
+        .filter_map(|id| ObjectId::with_string(id).ok())
+        .collect::<Vec<ObjectId>>();
+    let mut opt = Options::default();
+    opt.set_collect(collect);
+    opt.filter = Some(doc! { "_id": { "$in": oids }, "project_id": project_id });
+    let docs = mongo.find(opt).await?;
+    Ok(docs
+        .into_iter()
+        .filter_map(|d| Some((d.get_object_id("_id").ok()?.to_hex(), d)))
+        .collect())
+}
+
+// 查出用例引用的步骤及其接口，任何一个不存在都返回 Err(缺失的 id)
+async fn plan_case(
+    mongo: &MongoDb,
+    case: &Case,
+) -> tide::Result<Result<(Vec<Planned>, Vec<Planned>, Vec<Planned>), Vec<String>>> {
+    let step_docs = find_by_ids(mongo, &STEP, &case.project_id, &case.step_ids()).await?;
+    let mut steps = Vec::new();
+    for (id, d) in step_docs {
+        steps.push((id, from_document::<Step>(d)?));
+    }
+    let interface_ids = steps.iter().map(|(_, s)| &s.interface_id).collect::<Vec<_>>();
+    let interface_docs = find_by_ids(mongo, &INTERFACE, &case.project_id, &interface_ids).await?;
+    let mut interfaces = Vec::new();
+    for (id, d) in interface_docs {
+        interfaces.push((id, from_document::<Interface>(d)?));
+    }
+
+    let mut missing = Vec::new();
+    let mut plan = |ids: &[String]| -> Vec<Planned> {
+        let mut res = Vec::new();
+        for id in ids {
+            let step = match steps.iter().find(|(sid, _)| sid == id) {
+                Some((_, step)) => step,
+                None => {
+                    missing.push(id.clone());
+                    continue;
+                }
+            };
+            match interfaces.iter().find(|(iid, _)| iid == &step.interface_id) {
+                Some((_, interface)) => res.push(Planned {
+                    step_id: id.clone(),
+                    step: step.clone(),
+                    interface: interface.clone(),
+                }),
+                None => missing.push(step.interface_id.clone()),
+            }
+        }
+        res
+    };
+    let setup = plan(&case.setup);
+    let main = plan(&case.steps);
+    let teardown = plan(&case.teardown);
+    if !missing.is_empty() {
+        return Ok(Err(missing));
+    }
+    Ok(Ok((setup, main, teardown)))
+}
+
+pub(crate) async fn run_case(mut req: Request<State>) -> tide::Result {
+    let data: RunCase = match req.body_json().await {
+        Ok(data) => data,
+        Err(error) => {
+            return Responser::new(Some(format!("{}", error)), &status::BAD_REQUEST).to_result()
+        }
+    };
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let project_id = req.param::<String>("pid")?;
+    let id = req.param::<String>("id")?;
+    let oid = match ObjectId::with_string(&id) {
+        Ok(oid) => oid,
+        Err(e) => return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result(),
+    };
+
+    let mongo = &req.state().mongo;
+    let mut opt = Options::default();
+    opt.find_one_opt(&CASE, Some(doc! { "_id": oid, "project_id": project_id }));
+    let case = match mongo.find(opt).await?.pop() {
+        Some(d) => from_document::<Case>(d)?,
+        None => return Responser::new(Some("用例不存在"), &status::BAD_REQUEST).to_result(),
+    };
+    let (setup, steps, teardown) = match plan_case(mongo, &case).await? {
+        Ok(plan) => plan,
+        Err(missing) => return Responser::new(Some(missing), &status::BAD_REQUEST).to_result(),
+    };
+
+    let environment = match &data.env_id {
+        Some(env_id) => {
+            let env_oid = match ObjectId::with_string(env_id) {
+                Ok(oid) => oid,
+                Err(e) => {
+                    return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result()
+                }
+            };
+            let mut opt = Options::default();
+            let filter = doc! { "_id": env_oid, "project_id": &project_id };
+            opt.find_one_opt(&ENVIRONMENT, Some(filter));
+            match mongo.find(opt).await?.pop() {
+                Some(d) => Some(from_document::<Environment>(d)?),
+                None => return Responser::new(Some("环境不存在"), &status::BAD_REQUEST).to_result(),
+            }
+        }
+        None => None,
+    };
+    let base_url = match (data.base_url, &environment) {
+        (Some(url), _) => url,
+        (None, Some(env)) => env.base_url.clone(),
+        (None, None) => {
+            return Responser::new(Some("base_url or env_id required"), &status::BAD_REQUEST)
+                .to_result()
+        }
+    };
+
+    // 密文变量解密失败时不执行，避免步骤带着缺失的变量发出请求
+    let mut runner = Runner::new(&base_url);
+    let mut error = None;
+    let mut secrets = Vec::new();
+    if let Some(env) = &environment {
+        match env.runtime_variables() {
+            Ok(variables) => {
+                secrets = env
+                    .secrets
+                    .keys()
+                    .filter_map(|k| variables.get(k).and_then(|v| v.as_str()))
+                    .map(String::from)
+                    .collect();
+                runner = runner
+                    .with_variables(variables)
+                    .with_headers(env.headers.clone());
+            }
+            Err(keys) => {
+                error = Some(format!("secret could not be decrypted: {}", keys.join(", ")))
+            }
+        }
+    }
+    let mut results = match error {
+        None => runner.run(&setup, &steps, &teardown).await,
+        Some(_) => Vec::new(),
+    };
+    mask_secrets(&mut results, &secrets);
+    let run = Run {
+        case_id: id,
+        env_id: data.env_id,
+        base_url,
+        passed: error.is_none()
+            && results.len() == case.step_ids().len()
+            && results.iter().all(|r| r.passed),
+        error,
+        results,
+        project_id,
+        user_id,
+        create_at: Local::now(),
+    };
+    let mut opt = Options::default();
+    opt.set_collect(&RUN);
+    let run_id = mongo.insert_one(opt, &run).await?;
+    Responser::new(Some(ResRun { id: run_id, run }), &status::OK).to_result()
+}
+
+pub(crate) async fn get_run(req: Request<State>) -> tide::Result {
+    let project_id = req.param::<String>("pid")?;
+    let id = req.param::<String>("id")?;
+    let oid = match ObjectId::with_string(&id) {
+        Ok(oid) => oid,
+        Err(e) => return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result(),
+    };
+
+    let mut opt = Options::default();
+    opt.find_one_opt(&RUN, Some(doc! { "_id": oid, "project_id": project_id }));
+    let mut res = req.state().mongo.find(opt).await?;
+    match res.pop().and_then(to_res_run) {
+        Some(data) => Responser::new(Some(data), &status::OK).to_result(),
+        None => Responser::new(Some("执行记录不存在"), &status::BAD_REQUEST).to_result(),
+    }
+}
+
+pub(crate) async fn list_run(req: Request<State>) -> tide::Result {
+    let filter: GetRun = match req.query() {
+        Ok(res) => res,
+        Err(e) => return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result(),
+    };
+    let project_id = req.param::<String>("pid")?;
+    let id = req.param::<String>("id")?;
+
+    let skip = (filter.page.page_num.max(1) - 1) * filter.page.page_size;
+    let opt = Options::new(
+        &RUN,
+        Some(doc! { "case_id": id, "project_id": project_id }),
+        Some(filter.page.page_size as i64),
+        Some(skip as i64),
+        Some(doc! { "_id": -1 }),
+        Some(doc! { "results": 0 }),
+    );
+    let data: Vec<ResRun> = req
+        .state()
+        .mongo
+        .find(opt)
+        .await?
+        .into_iter()
+        .filter_map(to_res_run)
+        .collect();
+    Responser::new(Some(data), &status::OK).to_result()
+}
@@ -0,0 +1,71 @@
+use chrono::prelude::Local;
+use validator::Validate;
+
+use crate::models::{Case, Run};
+use crate::utils::Page;
+
+#[derive(Deserialize, Validate)]
+pub(crate) struct AddCase {
+    #[validate(length(min = 1, message = "name can not be empty"))]
+    pub(crate) name: String,
+    pub(crate) description: String,
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+    #[serde(default)]
+    pub(crate) setup: Vec<String>,
+    #[validate(length(min = 1, message = "steps can not be empty"))]
+    pub(crate) steps: Vec<String>,
+    #[serde(default)]
+    pub(crate) teardown: Vec<String>,
+}
+
+impl AddCase {
+    pub(crate) fn into_case(self, project_id: String, user_id: String) -> Case {
+        Case {
+            name: self.name,
+            description: self.description,
+            tags: self.tags,
+            setup: self.setup,
+            steps: self.steps,
+            teardown: self.teardown,
+            project_id,
+            user_id,
+            create_at: Local::now(),
+            update_at: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub(crate) struct GetCase {
+    pub(crate) name: Option<String>,
+    pub(crate) tag: Option<String>,
+    pub(crate) page: Page,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ResCase {
+    pub(crate) id: String,
+    #[serde(flatten)]
+    pub(crate) case: Case,
+}
+
+// 指定 env_id 时使用环境的 base_url，base_url 不为空时覆盖环境中的值
+#[derive(Deserialize, Validate)]
+pub(crate) struct RunCase {
+    pub(crate) env_id: Option<String>,
+    #[validate(url(message = "base_url error"))]
+    pub(crate) base_url: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub(crate) struct GetRun {
+    pub(crate) page: Page,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ResRun {
+    pub(crate) id: String,
+    #[serde(flatten)]
+    pub(crate) run: Run,
+}
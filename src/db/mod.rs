@@ -1,5 +1,12 @@
 mod mongo_db;
+mod project_db;
 mod redis_db;
+mod user_db;
 
-pub(crate) use crate::db::mongo_db::{MongoDb, Options};
-pub(crate) use crate::db::redis_db::Redis;
+pub(crate) use crate::db::mongo_db::{is_duplicate, MongoDb, Options};
+pub(crate) use crate::db::project_db::migrate_default_projects;
+pub(crate) use crate::db::redis_db::{
+    Redis, Session, CONFIRM_EXPIRE_TIME, EMAIL_CANCEL_TIME, INVITE_EXPIRE_TIME, LOGIN_LOCK_TIME,
+    MAGIC_EXPIRE_TIME, MFA_EXPIRE_TIME, OIDC_EXPIRE_TIME, RESET_EXPIRE_TIME,
+};
+pub(crate) use crate::db::user_db::{activate_verified, find_user, find_user_by, promote_admins};
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
 use async_std::stream::StreamExt;
-use mongodb::bson::{from_bson, oid::ObjectId, to_document, Bson, Document};
+use mongodb::bson::{doc, from_bson, oid::ObjectId, to_document, Bson, Document};
+use mongodb::error::{ErrorKind, WriteFailure};
 use mongodb::options::FindOptions;
 use mongodb::{Client, Database};
 use serde::Serialize;
@@ -27,6 +28,30 @@ impl MongoDb {
         })
     }
 
+    // 唯一索引，多个字段时为组合唯一，并发写入时由数据库保证不重复
+    pub(crate) async fn create_unique_index(
+        &self,
+        collect: &str,
+        fields: &[&str],
+    ) -> tide::Result<()> {
+        let mut key = Document::new();
+        for field in fields {
+            key.insert(*field, 1);
+        }
+        let name = format!("{}_unique", fields.join("_"));
+        let command = doc! {
+            "createIndexes": collect,
+            "indexes": [{ "key": key, "name": name, "unique": true }],
+        };
+        match self.db.run_command(command, None).await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                log::error!("Create Mongo Index Error: {:?}", e);
+                Err(tide::Error::new(StatusCode::InternalServerError, e))
+            }
+        }
+    }
+
     pub async fn find(&self, opt: Options) -> tide::Result<Vec<Document>> {
         let options = FindOptions::builder()
             .limit(opt.limit)
@@ -146,6 +171,14 @@ impl MongoDb {
     }
 }
 
+// 违反唯一索引
+pub(crate) fn is_duplicate(e: &tide::Error) -> bool {
+    match e.downcast_ref::<mongodb::error::Error>().map(|e| &*e.kind) {
+        Some(ErrorKind::Write(WriteFailure::WriteError(w))) => w.code == 11000,
+        _ => false,
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct Options {
     pub(crate) collect: String,
@@ -215,6 +248,9 @@ mod tests {
             password: String::from("123456"),
             phone: String::from("157"),
             active: false,
+            roles: Vec::new(),
+            mfa: None,
+            email_change: None,
             create_at: now,
             update_at: Some(now),
         };
@@ -240,6 +276,9 @@ mod tests {
             password: String::from("123456"),
             phone: String::from("157"),
             active: false,
+            roles: Vec::new(),
+            mfa: None,
+            email_change: None,
             create_at: now,
             update_at: Some(now),
         };
@@ -250,6 +289,9 @@ mod tests {
             password: String::from("123456"),
             phone: String::from("157"),
             active: false,
+            roles: Vec::new(),
+            mfa: None,
+            email_change: None,
             create_at: now,
             update_at: Some(now),
         };
@@ -289,6 +331,9 @@ mod tests {
             password: String::from("123456"),
             phone: String::from("157"),
             active: false,
+            roles: Vec::new(),
+            mfa: None,
+            email_change: None,
             create_at: now,
             update_at: Some(now),
         };
@@ -298,6 +343,9 @@ mod tests {
             password: String::from("123456"),
             phone: String::from("157"),
             active: false,
+            roles: Vec::new(),
+            mfa: None,
+            email_change: None,
             create_at: now,
             update_at: Some(now),
         };
@@ -332,6 +380,9 @@ mod tests {
             password: String::from("123456"),
             phone: String::from("157"),
             active: false,
+            roles: Vec::new(),
+            mfa: None,
+            email_change: None,
             create_at: now,
             update_at: Some(now),
         };
@@ -360,6 +411,9 @@ mod tests {
             password: String::from("123456"),
             phone: String::from("157"),
             active: false,
+            roles: Vec::new(),
+            mfa: None,
+            email_change: None,
             create_at: now,
             update_at: Some(now),
         };
@@ -370,6 +424,9 @@ mod tests {
             password: String::from("123456"),
             phone: String::from("157"),
             active: false,
+            roles: Vec::new(),
+            mfa: None,
+            email_change: None,
             create_at: now,
             update_at: Some(now),
         };
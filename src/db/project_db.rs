@@ -0,0 +1,56 @@
+use mongodb::bson::{doc, Document};
+use tide::log;
+
+use super::mongo_db::{MongoDb, Options};
+use crate::models::{Project, CASE, ENVIRONMENT, INTERFACE, PROJECT, RUN, STEP};
+
+// 引入项目之前的记录没有 project_id
+fn orphan_filter() -> Document {
+    doc! { "$or": [{ "project_id": { "$exists": false } }, { "project_id": "" }] }
+}
+
+// 把没有项目的记录归入创建者的默认项目，启动时执行，迁移过的记录不会再处理
+pub(crate) async fn migrate_default_projects(mongo: &MongoDb) -> tide::Result<()> {
+    let collects = [&*INTERFACE, &*STEP, &*CASE, &*ENVIRONMENT, &*RUN];
+    let mut user_ids: Vec<String> = Vec::new();
+    for collect in collects.iter() {
+        let mut opt = Options::default();
+        opt.set_collect(collect);
+        opt.filter = Some(orphan_filter());
+        opt.fileds = Some(doc! { "user_id": 1 });
+        for data in mongo.find(opt).await? {
+            if let Ok(user_id) = data.get_str("user_id") {
+                if !user_ids.iter().any(|u| u == user_id) {
+                    user_ids.push(user_id.to_string());
+                }
+            }
+        }
+    }
+
+    for user_id in user_ids {
+        let project_id = default_project(mongo, &user_id).await?;
+        let mut filter = orphan_filter();
+        filter.insert("user_id", &user_id);
+        for collect in collects.iter() {
+            let mut opt = Options::default();
+            opt.update_opt(collect, Some(filter.clone()), Some(0));
+            let update = doc! { "$set": { "project_id": &project_id } };
+            let count = mongo.update(update, opt).await?;
+            log::info!("move {} {} of {} into project {}", count, collect, user_id, project_id);
+        }
+    }
+    Ok(())
+}
+
+// 迁移中断后重新执行时沿用已经建好的默认项目
+async fn default_project(mongo: &MongoDb, user_id: &str) -> tide::Result<String> {
+    let project = Project::default_for(user_id);
+    let mut opt = Options::default();
+    opt.find_one_opt(&PROJECT, Some(doc! { "user_id": user_id, "name": &project.name }));
+    if let Some(data) = mongo.find(opt).await?.pop() {
+        return Ok(data.get_object_id("_id")?.to_hex());
+    }
+    let mut opt = Options::default();
+    opt.set_collect(&PROJECT);
+    mongo.insert_one(opt, &project).await
+}
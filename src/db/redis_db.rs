@@ -1,12 +1,135 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use chrono::prelude::{DateTime, Local};
 use redis::{aio::Connection, AsyncCommands, Client};
 use tide::{log, StatusCode};
 
-use crate::middleware::Token;
-use crate::utils::rand_str;
+use crate::middleware::TokenPair;
+use crate::models::Role;
+use crate::utils::{my_date_format, rand_str, Claims, KEYSET};
+use crate::CONFIG;
 
 lazy_static! {
     pub(crate) static ref TOKEN_SIZE: usize = 32;
-    pub(crate) static ref EXPIRE_TIME: usize = 60 * 60 * 12;
+    pub(crate) static ref EXPIRE_TIME: usize = 60 * 30;
+    pub(crate) static ref REFRESH_EXPIRE_TIME: usize = 60 * 60 * 24 * 30;
+    pub(crate) static ref RESET_EXPIRE_TIME: usize = 60 * 30;
+    pub(crate) static ref CONFIRM_EXPIRE_TIME: usize = 60 * 60 * 24;
+    pub(crate) static ref INVITE_EXPIRE_TIME: usize = 60 * 60 * 24 * 7;
+    pub(crate) static ref MFA_EXPIRE_TIME: usize = 60 * 5;
+    pub(crate) static ref OIDC_EXPIRE_TIME: usize = 60 * 10;
+    pub(crate) static ref MAGIC_EXPIRE_TIME: usize = 60 * 10;
+    // 同一用途的邮件发送间隔
+    pub(crate) static ref EMAIL_COOLDOWN_TIME: usize = 60;
+    // 修改邮箱后旧邮箱可以撤销的时间
+    pub(crate) static ref EMAIL_CANCEL_TIME: usize = 60 * 60 * 24 * 7;
+    // 前几次失败不限制，之后等待时间翻倍，达到锁定次数后锁定一段时间
+    pub(crate) static ref LOGIN_FREE_ATTEMPTS: usize = 3;
+    pub(crate) static ref LOGIN_LOCK_ATTEMPTS: usize = 10;
+    pub(crate) static ref LOGIN_LOCK_TIME: usize = 60 * 15;
+    // 失败次数的统计周期，每次失败后重新计时
+    pub(crate) static ref LOGIN_FAIL_WINDOW: usize = 60 * 60;
+    // 同一 IP 可能有多个帐号，次数放宽
+    pub(crate) static ref LOGIN_IP_FACTOR: usize = 5;
+}
+
+// 一次登录产生一个 family，刷新时 family 不变，access 和 refresh 轮换
+fn family_key(family: &str) -> String {
+    format!("family:{}", family)
+}
+
+// 帐号的所有 session，field 为 family，value 为 Session
+fn sessions_key(id: &str) -> String {
+    format!("sessions:{}", id)
+}
+
+// 非 JWT 模式下 access token 所属的 family，与 access token 同时过期
+fn access_family_key(access: &str) -> String {
+    format!("access:family:{}", access)
+}
+
+// JWT 模式下被撤销的 token，按 jti 记录
+fn deny_key(jti: &str) -> String {
+    format!("jwt:deny:{}", jti)
+}
+
+fn refresh_key(token: &str) -> String {
+    format!("refresh:{}", token)
+}
+
+// 已经使用过的 refresh token，用于发现重复使用
+fn refresh_used_key(token: &str) -> String {
+    format!("refresh:used:{}", token)
+}
+
+// 一次性 token 按用途分开存放，与 session token 互不影响
+fn once_key(kind: &str, token: &str) -> String {
+    format!("{}:{}", kind, token)
+}
+
+fn once_user_key(kind: &str, id: &str) -> String {
+    format!("{}:user:{}", kind, id)
+}
+
+fn once_fail_key(kind: &str, token: &str) -> String {
+    format!("{}:fail:{}", kind, token)
+}
+
+// 发送过邮件的帐号，kind 为 resend 或 forget
+fn email_sent_key(kind: &str, id: &str) -> String {
+    format!("{}:{}", kind, id)
+}
+
+// scope 为 account 或 ip
+fn login_fail_key(scope: &str, value: &str) -> String {
+    format!("login:fail:{}:{}", scope, value)
+}
+
+fn login_lock_key(scope: &str, value: &str) -> String {
+    format!("login:lock:{}:{}", scope, value)
+}
+
+// 第 failures 次失败后需要等待的秒数
+fn backoff(failures: usize, free: usize, lock: usize) -> usize {
+    if failures < free {
+        0
+    } else if failures >= lock {
+        *LOGIN_LOCK_TIME
+    } else {
+        (1 << (failures - free)).min(*LOGIN_LOCK_TIME)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Session {
+    pub id: String,
+    #[serde(with = "my_date_format")]
+    pub create_at: DateTime<Local>,
+    #[serde(with = "my_date_format")]
+    pub last_seen: DateTime<Local>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+// 返回给前端的 session，current 表示是否为当前请求使用的 session
+#[derive(Serialize)]
+pub struct SessionInfo {
+    #[serde(flatten)]
+    pub session: Session,
+    pub current: bool,
+}
+
+impl Session {
+    pub fn new(user_agent: Option<String>, ip: Option<String>) -> Self {
+        Self {
+            id: rand_str(*TOKEN_SIZE),
+            create_at: Local::now(),
+            last_seen: Local::now(),
+            user_agent,
+            ip,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -37,11 +160,465 @@ impl Redis {
         }
     }
 
-    pub async fn set_token(&self, id: String) -> tide::Result<Token> {
+    // 登录后新建一个 session，并签发 access 和 refresh token
+    pub async fn set_token(
+        &self,
+        id: String,
+        roles: &[Role],
+        session: Session,
+    ) -> tide::Result<TokenPair> {
+        let mut redis_col = self.connection().await?;
+        save_session(&mut redis_col, &id, &session).await?;
+        issue(&mut redis_col, &id, roles, &session.id).await
+    }
+
+    pub async fn is_denied(&self, jti: &str) -> tide::Result<bool> {
+        let mut redis_col = self.connection().await?;
+        Ok(redis_col.exists(deny_key(jti)).await?)
+    }
+
+    // 用 refresh token 换新的一对 token，旧的立即失效
+    pub async fn refresh_token(&self, refresh: &str) -> tide::Result<Option<TokenPair>> {
+        let mut redis_col = self.connection().await?;
+        let key = refresh_key(refresh);
+        let (family, deleted): (Option<String>, usize) = redis::pipe()
+            .atomic()
+            .get(&key)
+            .del(&key)
+            .query_async(&mut redis_col)
+            .await?;
+        let family = match family {
+            Some(family) if deleted > 0 => family,
+            _ => {
+                // 用过的 refresh token 再次出现，说明可能已泄露，整个 session 作废
+                let used: Option<String> = redis_col.get(refresh_used_key(refresh)).await?;
+                if let Some(family) = used {
+                    log::warn!("refresh token reused, revoke family {}", family);
+                    revoke_family(&mut redis_col, &family).await?;
+                }
+                return Ok(None);
+            }
+        };
+
+        let _: () = redis_col
+            .set_ex(refresh_used_key(refresh), &family, *REFRESH_EXPIRE_TIME)
+            .await?;
+        let (id, access, roles): (Option<String>, Option<String>, Option<String>) = redis_col
+            .hget(family_key(&family), &["id", "access", "roles"])
+            .await?;
+        let id = match id {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        if let Some(access) = access {
+            revoke_access(&mut redis_col, &access).await?;
+        }
+        let roles: Vec<Role> = roles
+            .and_then(|r| serde_json::from_str(&r).ok())
+            .unwrap_or_default();
+
+        touch(&mut redis_col, &id, &family).await?;
+        issue(&mut redis_col, &id, &roles, &family).await.map(Some)
+    }
+
+    // 列出帐号的 session，顺便清理已经过期的
+    pub async fn sessions(&self, id: &str, access: Option<&str>) -> tide::Result<Vec<SessionInfo>> {
+        let mut redis_col = self.connection().await?;
+        let access = match access {
+            Some(access) => resolve_access(&mut redis_col, access).await?.map(|(_, _, f)| f),
+            None => None,
+        };
+        let key = sessions_key(id);
+        let data: HashMap<String, String> = redis_col.hgetall(&key).await?;
+        let mut res = Vec::new();
+        for (family, value) in data {
+            let current: Option<String> = redis_col.hget(family_key(&family), "access").await?;
+            let session = match serde_json::from_str::<Session>(&value) {
+                Ok(session) if current.is_some() => session,
+                _ => {
+                    let _: () = redis_col.hdel(&key, &family).await?;
+                    continue;
+                }
+            };
+            res.push(SessionInfo {
+                session,
+                current: access.as_deref() == Some(family.as_str()),
+            });
+        }
+        res.sort_by_key(|s| Reverse(s.session.last_seen));
+        Ok(res)
+    }
+
+    // 删除帐号的某个 session
+    pub async fn revoke_session(&self, id: &str, session: &str) -> tide::Result<bool> {
+        let mut redis_col = self.connection().await?;
+        let exist: bool = redis_col.hexists(sessions_key(id), session).await?;
+        if exist {
+            revoke_family(&mut redis_col, session).await?;
+        }
+        Ok(exist)
+    }
+
+    // 退出登录，删除 access token 所属的 session
+    pub async fn logout(&self, access: &str) -> tide::Result<bool> {
+        let mut redis_col = self.connection().await?;
+        let (_, access, family) = match resolve_access(&mut redis_col, access).await? {
+            Some(res) => res,
+            None => return Ok(false),
+        };
+        revoke_family(&mut redis_col, &family).await?;
+        revoke_access(&mut redis_col, &access).await?;
+        Ok(true)
+    }
+
+    // access token 所属的帐号和 session family
+    pub async fn access_session(&self, access: &str) -> tide::Result<Option<(String, String)>> {
+        let mut redis_col = self.connection().await?;
+        let res = resolve_access(&mut redis_col, access).await?;
+        Ok(res.map(|(id, _, family)| (id, family)))
+    }
+
+    // 每次请求时更新 session 的最后活跃时间，JWT 模式下不调用
+    pub async fn touch_session(&self, id: &str, family: &str) -> tide::Result<()> {
+        let mut redis_col = self.connection().await?;
+        touch(&mut redis_col, id, family).await
+    }
+
+    // 删除帐号的所有 session
+    pub async fn clear_tokens(&self, id: &str) -> tide::Result<()> {
+        let mut redis_col = self.connection().await?;
+        let families: Vec<String> = redis_col.hkeys(sessions_key(id)).await?;
+        for family in families {
+            revoke_family(&mut redis_col, &family).await?;
+        }
+        let _: () = redis_col.del(sessions_key(id)).await?;
+        Ok(())
+    }
+
+    // 删除帐号除 access token 所属 session 以外的所有 session
+    pub async fn clear_other_tokens(&self, id: &str, access: &str) -> tide::Result<()> {
+        let mut redis_col = self.connection().await?;
+        let current = resolve_access(&mut redis_col, access).await?.map(|(_, _, f)| f);
+        let families: Vec<String> = redis_col.hkeys(sessions_key(id)).await?;
+        for family in families {
+            if current.as_deref() != Some(family.as_str()) {
+                revoke_family(&mut redis_col, &family).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // 还需要等待的秒数，帐号和 IP 取较大值
+    pub async fn login_wait(&self, email: &str, ip: Option<&str>) -> tide::Result<usize> {
+        let mut redis_col = self.connection().await?;
+        let mut keys = vec![login_lock_key("account", email)];
+        if let Some(ip) = ip {
+            keys.push(login_lock_key("ip", ip));
+        }
+        let mut wait = 0;
+        for key in keys {
+            // key 不存在时为 -2
+            let ttl: isize = redis_col.ttl(key).await?;
+            wait = wait.max(ttl.max(0) as usize);
+        }
+        Ok(wait)
+    }
+
+    // 记录一次失败，返回帐号是否因此被锁定
+    pub async fn login_failed(&self, email: &str, ip: Option<&str>) -> tide::Result<bool> {
+        let mut redis_col = self.connection().await?;
+        let free = *LOGIN_FREE_ATTEMPTS;
+        let lock = *LOGIN_LOCK_ATTEMPTS;
+        let mut scopes = vec![("account", email, free, lock)];
+        if let Some(ip) = ip {
+            scopes.push(("ip", ip, free * *LOGIN_IP_FACTOR, lock * *LOGIN_IP_FACTOR));
+        }
+        let mut locked = false;
+        for (scope, value, free, lock) in scopes {
+            let key = login_fail_key(scope, value);
+            let count: usize = redis_col.incr(&key, 1).await?;
+            let _: () = redis_col.expire(&key, *LOGIN_FAIL_WINDOW).await?;
+            let wait = backoff(count, free, lock);
+            if wait > 0 {
+                let _: () = redis_col.set_ex(login_lock_key(scope, value), 1, wait).await?;
+            }
+            locked |= scope == "account" && count == lock;
+        }
+        Ok(locked)
+    }
+
+    // 登录成功后清除帐号的失败记录，IP 的记录保留
+    pub async fn login_succeeded(&self, email: &str) -> tide::Result<()> {
+        let mut redis_col = self.connection().await?;
+        let keys = vec![login_fail_key("account", email), login_lock_key("account", email)];
+        let _: () = redis_col.del(keys).await?;
+        Ok(())
+    }
+
+    // 同一帐号同一用途只保留最新的 token
+    pub async fn set_once_token(
+        &self,
+        kind: &str,
+        id: &str,
+        expire: usize,
+    ) -> tide::Result<String> {
         let token_str = rand_str(*TOKEN_SIZE);
         let mut redis_col = self.connection().await?;
-        redis_col.set_ex(&token_str, &id, *EXPIRE_TIME).await?;
-        redis_col.set_ex(&id, &token_str, *EXPIRE_TIME).await?;
-        Ok(Token { token: token_str })
+        let old: Option<String> = redis_col.get(once_user_key(kind, id)).await?;
+        if let Some(old) = old {
+            let _: () = redis_col.del(once_key(kind, &old)).await?;
+        }
+        let _: () = redis_col.set_ex(once_key(kind, &token_str), id, expire).await?;
+        let _: () = redis_col.set_ex(once_user_key(kind, id), &token_str, expire).await?;
+        Ok(token_str)
+    }
+
+    // 只查看不删除，校验通过后再用 take_once_token 取出
+    pub async fn peek_once_token(&self, kind: &str, token: &str) -> tide::Result<Option<String>> {
+        let mut redis_col = self.connection().await?;
+        Ok(redis_col.get(once_key(kind, token)).await?)
+    }
+
+    // 记录一次校验失败，达到 max 次后 token 作废
+    pub async fn fail_once_token(&self, kind: &str, token: &str, max: usize) -> tide::Result<()> {
+        let mut redis_col = self.connection().await?;
+        let key = once_fail_key(kind, token);
+        let count: usize = redis_col.incr(&key, 1).await?;
+        let _: () = redis_col.expire(&key, *EXPIRE_TIME).await?;
+        if count >= max {
+            self.take_once_token(kind, token).await?;
+        }
+        Ok(())
+    }
+
+    // 取出后立即删除，保证 token 只能使用一次
+    pub async fn take_once_token(&self, kind: &str, token: &str) -> tide::Result<Option<String>> {
+        let mut redis_col = self.connection().await?;
+        let key = once_key(kind, token);
+        let (id, deleted): (Option<String>, usize) = redis::pipe()
+            .atomic()
+            .get(&key)
+            .del(&key)
+            .query_async(&mut redis_col)
+            .await?;
+        let id = match id {
+            Some(id) if deleted > 0 => id,
+            _ => return Ok(None),
+        };
+        let _: () = redis_col.del(once_user_key(kind, &id)).await?;
+        Ok(Some(id))
+    }
+
+    // 同一帐号同一用途的邮件在间隔内只发一次，返回这次是否可以发送
+    pub async fn email_cooldown(&self, kind: &str, id: &str) -> tide::Result<bool> {
+        let mut redis_col = self.connection().await?;
+        let sent: Option<String> = redis::cmd("SET")
+            .arg(email_sent_key(kind, id))
+            .arg(1)
+            .arg("EX")
+            .arg(*EMAIL_COOLDOWN_TIME)
+            .arg("NX")
+            .query_async(&mut redis_col)
+            .await?;
+        Ok(sent.is_some())
+    }
+}
+
+// 返回帐号、family 中记录的 access 标识和 family，JWT 模式下直接取 claims，标识为 jti
+async fn resolve_access(
+    con: &mut Connection,
+    access: &str,
+) -> tide::Result<Option<(String, String, String)>> {
+    if CONFIG.jwt.enabled {
+        return Ok(KEYSET.decode(access).ok().map(|c| (c.sub, c.jti, c.sid)));
+    }
+    let (id, family): (Option<String>, Option<String>) = con
+        .get(&[access.to_string(), access_family_key(access)])
+        .await?;
+    Ok(match (id, family) {
+        (Some(id), Some(family)) => Some((id, access.to_string(), family)),
+        _ => None,
+    })
+}
+
+async fn revoke_access(con: &mut Connection, access: &str) -> tide::Result<()> {
+    if CONFIG.jwt.enabled {
+        // JWT 无法收回，在有效期内加入黑名单
+        let _: () = con.set_ex(deny_key(access), 1, *EXPIRE_TIME).await?;
+    } else {
+        let _: () = con.del(&[access.to_string(), access_family_key(access)]).await?;
+    }
+    Ok(())
+}
+
+async fn issue(
+    con: &mut Connection,
+    id: &str,
+    roles: &[Role],
+    family: &str,
+) -> tide::Result<TokenPair> {
+    let refresh = rand_str(*TOKEN_SIZE);
+    let (token, access) = if CONFIG.jwt.enabled {
+        let claims = Claims::new(id, roles, family, *EXPIRE_TIME);
+        let token = KEYSET
+            .encode(&claims)
+            .map_err(|e| tide::Error::from_str(StatusCode::InternalServerError, e))?;
+        (token, claims.jti)
+    } else {
+        let token = rand_str(*TOKEN_SIZE);
+        (token.clone(), token)
+    };
+
+    let key = family_key(family);
+    let roles = serde_json::to_string(roles)?;
+    let fields = [
+        ("id", id),
+        ("access", access.as_str()),
+        ("refresh", refresh.as_str()),
+        ("roles", roles.as_str()),
+    ];
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    // JWT 模式下校验不需要查 redis
+    if !CONFIG.jwt.enabled {
+        pipe.set_ex(&access, id, *EXPIRE_TIME)
+            .ignore()
+            .set_ex(access_family_key(&access), family, *EXPIRE_TIME)
+            .ignore();
+    }
+    let _: () = pipe
+        .set_ex(refresh_key(&refresh), family, *REFRESH_EXPIRE_TIME)
+        .ignore()
+        .hset_multiple(&key, &fields)
+        .ignore()
+        .expire(&key, *REFRESH_EXPIRE_TIME)
+        .ignore()
+        .query_async(con)
+        .await?;
+    Ok(TokenPair {
+        access_token: token,
+        refresh_token: refresh,
+        expires_in: *EXPIRE_TIME,
+    })
+}
+
+// 更新最后活跃时间，一分钟内只写一次
+async fn touch(con: &mut Connection, id: &str, family: &str) -> tide::Result<()> {
+    let data: Option<String> = con.hget(sessions_key(id), family).await?;
+    if let Some(mut session) = data.and_then(|d| serde_json::from_str::<Session>(&d).ok()) {
+        let now = Local::now();
+        if (now - session.last_seen).num_seconds() >= 60 {
+            session.last_seen = now;
+            save_session(con, id, &session).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn save_session(con: &mut Connection, id: &str, session: &Session) -> tide::Result<()> {
+    let key = sessions_key(id);
+    let _: () = redis::pipe()
+        .atomic()
+        .hset(&key, &session.id, serde_json::to_string(session)?)
+        .ignore()
+        .expire(&key, *REFRESH_EXPIRE_TIME)
+        .ignore()
+        .query_async(con)
+        .await?;
+    Ok(())
+}
+
+async fn revoke_family(con: &mut Connection, family: &str) -> tide::Result<()> {
+    let key = family_key(family);
+    let (id, access, refresh): (Option<String>, Option<String>, Option<String>) =
+        con.hget(&key, &["id", "access", "refresh"]).await?;
+    if let Some(access) = access {
+        revoke_access(con, &access).await?;
+    }
+    let mut keys = vec![key];
+    keys.extend(refresh.as_deref().map(refresh_key));
+    let _: () = con.del(keys).await?;
+    if let Some(id) = id {
+        let _: () = con.hdel(sessions_key(&id), family).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backoff, Redis, Session, LOGIN_LOCK_TIME, RESET_EXPIRE_TIME};
+    use crate::utils::rand_str;
+    use crate::CONFIG;
+
+    #[test]
+    fn test_backoff() {
+        let waits: Vec<usize> = (1..=11).map(|n| backoff(n, 3, 10)).collect();
+        let lock = *LOGIN_LOCK_TIME;
+        assert_eq!(waits, vec![0, 0, 1, 2, 4, 8, 16, 32, 64, lock, lock]);
+    }
+
+    // 忘记密码：同一帐号只保留最新的重置 token，只能使用一次，重置后 session 全部失效
+    #[async_std::test]
+    async fn test_reset_token() {
+        let redis = Redis::new(&CONFIG.database.redis_url).unwrap();
+        let id = rand_str(24);
+        assert!(redis.email_cooldown("forget", &id).await.unwrap());
+        assert!(!redis.email_cooldown("forget", &id).await.unwrap());
+
+        let old = redis.set_once_token("reset", &id, *RESET_EXPIRE_TIME).await.unwrap();
+        let token = redis.set_once_token("reset", &id, *RESET_EXPIRE_TIME).await.unwrap();
+        assert_eq!(redis.peek_once_token("reset", &old).await.unwrap(), None);
+        assert_eq!(redis.peek_once_token("reset", &token).await.unwrap(), Some(id.clone()));
+        assert_eq!(redis.take_once_token("reset", &token).await.unwrap(), Some(id.clone()));
+        assert_eq!(redis.take_once_token("reset", &token).await.unwrap(), None);
+
+        redis.set_token(id.clone(), &[], Session::new(None, None)).await.unwrap();
+        redis.set_token(id.clone(), &[], Session::new(None, None)).await.unwrap();
+        assert_eq!(redis.sessions(&id, None).await.unwrap().len(), 2);
+        redis.clear_tokens(&id).await.unwrap();
+        assert!(redis.sessions(&id, None).await.unwrap().is_empty());
+    }
+
+    // access token 直接找到所属的 session，不需要遍历帐号的所有 session
+    #[async_std::test]
+    async fn test_access_session() {
+        let redis = Redis::new(&CONFIG.database.redis_url).unwrap();
+        let id = rand_str(24);
+        let session = Session::new(None, None);
+        let family = session.id.clone();
+        let pair = redis.set_token(id.clone(), &[], session).await.unwrap();
+        redis.set_token(id.clone(), &[], Session::new(None, None)).await.unwrap();
+
+        let res = redis.access_session(&pair.access_token).await.unwrap();
+        assert_eq!(res, Some((id.clone(), family.clone())));
+        let sessions = redis.sessions(&id, Some(&pair.access_token)).await.unwrap();
+        let current: Vec<_> = sessions.iter().filter(|s| s.current).collect();
+        assert_eq!(current.len(), 1);
+        assert_eq!(current[0].session.id, family);
+
+        assert!(redis.logout(&pair.access_token).await.unwrap());
+        assert_eq!(redis.sessions(&id, None).await.unwrap().len(), 1);
+        redis.clear_tokens(&id).await.unwrap();
+    }
+
+    // 登录状态下修改密码，只保留当前 session
+    #[async_std::test]
+    async fn test_clear_other_tokens() {
+        let redis = Redis::new(&CONFIG.database.redis_url).unwrap();
+        let id = rand_str(24);
+        let session = Session::new(None, None);
+        let family = session.id.clone();
+        let pair = redis.set_token(id.clone(), &[], session).await.unwrap();
+        let other = redis.set_token(id.clone(), &[], Session::new(None, None)).await.unwrap();
+        redis.set_token(id.clone(), &[], Session::new(None, None)).await.unwrap();
+
+        redis.clear_other_tokens(&id, &pair.access_token).await.unwrap();
+        let sessions = redis.sessions(&id, Some(&pair.access_token)).await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(sessions[0].current);
+        assert_eq!(sessions[0].session.id, family);
+        assert_eq!(redis.access_session(&other.access_token).await.unwrap(), None);
+        assert!(redis.refresh_token(&other.refresh_token).await.unwrap().is_none());
+        redis.clear_tokens(&id).await.unwrap();
     }
 }
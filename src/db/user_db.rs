@@ -0,0 +1,141 @@
+use mongodb::bson::{doc, from_document, oid::ObjectId, to_bson, Document};
+
+use super::mongo_db::{MongoDb, Options};
+use super::redis_db::Redis;
+use crate::models::{Role, User, API_KEY, USER};
+use crate::utils::{hash_password, rand_str};
+
+// 按条件查询一个帐号，返回 id 和帐号
+pub(crate) async fn find_user_by(
+    mongo: &MongoDb,
+    filter: Document,
+) -> tide::Result<Option<(String, User)>> {
+    let mut opt = Options::default();
+    opt.find_one_opt(&USER, Some(filter));
+    match mongo.find(opt).await?.pop() {
+        Some(data) => {
+            let id = data.get_object_id("_id")?.to_hex();
+            Ok(Some((id, from_document(data)?)))
+        }
+        None => Ok(None),
+    }
+}
+
+pub(crate) async fn find_user(mongo: &MongoDb, id: &str) -> tide::Result<Option<User>> {
+    let filter = doc! { "_id": ObjectId::with_string(id)? };
+    Ok(find_user_by(mongo, filter).await?.map(|(_, user)| user))
+}
+
+// 邮箱验证通过后激活帐号。未激活的帐号可能是他人抢先用该邮箱注册的，作废原密码、session 和 API key
+pub(crate) async fn activate_verified(
+    mongo: &MongoDb,
+    redis: &Redis,
+    id: &str,
+    user: &User,
+) -> tide::Result<()> {
+    if user.active {
+        return Ok(());
+    }
+    let update = doc! { "$set": { "active": true, "password": hash_password(&rand_str(32))? } };
+    let mut opt = Options::default();
+    opt.update_opt(&USER, Some(doc! { "_id": ObjectId::with_string(id)? }), None);
+    mongo.update(update, opt).await?;
+    redis.clear_tokens(id).await?;
+    let mut opt = Options::default();
+    opt.del_opt(&API_KEY, Some(doc! { "user_id": id }), Some(0));
+    mongo.delete(opt).await?;
+    Ok(())
+}
+
+// 已有帐号中邮箱在管理员列表里的设置为管理员
+pub(crate) async fn promote_admins(mongo: &MongoDb, emails: &[String]) -> tide::Result<i64> {
+    if emails.is_empty() {
+        return Ok(0);
+    }
+    let mut list: Vec<String> = emails.iter().map(|e| e.to_lowercase()).collect();
+    list.extend(emails.iter().cloned());
+    let filter = doc! { "email": { "$in": list }, "roles": { "$ne": "admin" } };
+    let update = doc! { "$set": { "roles": to_bson(&vec![Role::Admin])? } };
+    let mut opt = Options::default();
+    opt.update_opt(&USER, Some(filter), Some(0));
+    mongo.update(update, opt).await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::Local;
+    use mongodb::bson::doc;
+
+    use super::{activate_verified, find_user};
+    use crate::db::{MongoDb, Options, Redis, Session};
+    use crate::models::{User, API_KEY, USER};
+    use crate::utils::{hash_password, password_verify};
+    use crate::CONFIG;
+
+    fn user(email: &str, active: bool) -> User {
+        User {
+            username: String::from("lomect"),
+            email: email.to_string(),
+            password: hash_password("Passw0rd").unwrap(),
+            phone: String::new(),
+            active,
+            roles: Vec::new(),
+            mfa: None,
+            email_change: None,
+            create_at: Local::now(),
+            update_at: None,
+        }
+    }
+
+    async fn insert(mongo: &MongoDb, redis: &Redis, user: &User) -> String {
+        let mut opt = Options::default();
+        opt.set_collect(&USER);
+        let id = mongo.insert_one(opt, user).await.unwrap();
+        redis.set_token(id.clone(), &[], Session::new(None, None)).await.unwrap();
+        let mut opt = Options::default();
+        opt.set_collect(&API_KEY);
+        mongo.insert_one(opt, &doc! { "user_id": &id }).await.unwrap();
+        id
+    }
+
+    async fn api_keys(mongo: &MongoDb, id: &str) -> usize {
+        let mut opt = Options::default();
+        opt.set_collect(&API_KEY);
+        opt.filter = Some(doc! { "user_id": id });
+        mongo.find(opt).await.unwrap().len()
+    }
+
+    // 抢先注册的未激活帐号被真正的邮箱主人激活时，原来的凭据全部作废
+    #[async_std::test]
+    async fn test_activate_verified() {
+        let mongo = MongoDb::new(&CONFIG.database.mongo_url, "test").await.unwrap();
+        let redis = Redis::new(&CONFIG.database.redis_url).unwrap();
+
+        let pending = user("pending@example.com", false);
+        let id = insert(&mongo, &redis, &pending).await;
+        activate_verified(&mongo, &redis, &id, &pending).await.unwrap();
+        let activated = find_user(&mongo, &id).await.unwrap().unwrap();
+        assert!(activated.active);
+        assert!(!password_verify(&activated.password, "Passw0rd"));
+        assert!(redis.sessions(&id, None).await.unwrap().is_empty());
+        assert_eq!(api_keys(&mongo, &id).await, 0);
+
+        // 已激活的帐号不受影响
+        let active = user("active@example.com", true);
+        let id = insert(&mongo, &redis, &active).await;
+        activate_verified(&mongo, &redis, &id, &active).await.unwrap();
+        let unchanged = find_user(&mongo, &id).await.unwrap().unwrap();
+        assert!(password_verify(&unchanged.password, "Passw0rd"));
+        assert_eq!(redis.sessions(&id, None).await.unwrap().len(), 1);
+        assert_eq!(api_keys(&mongo, &id).await, 1);
+
+        redis.clear_tokens(&id).await.unwrap();
+        let emails = vec![pending.email, active.email];
+        let mut opt = Options::default();
+        opt.del_opt(&USER, Some(doc! { "email": { "$in": emails } }), Some(0));
+        mongo.delete(opt).await.unwrap();
+        let mut opt = Options::default();
+        opt.del_opt(&API_KEY, Some(doc! { "user_id": &id }), Some(0));
+        mongo.delete(opt).await.unwrap();
+    }
+}
@@ -0,0 +1,26 @@
+mod routers;
+mod schema;
+
+use tide::Server;
+
+use crate::State;
+use routers::{
+    add_environment, delete_environment, get_environment, list_environment, update_environment,
+};
+use crate::middleware::{guard, LoginMiddleware, ProjectMiddleware};
+
+pub(crate) fn environment_router(app: &mut Server<State>) {
+    let mut environment = app.at("/project/:pid/environment");
+    environment.with(LoginMiddleware).with(ProjectMiddleware);
+    environment
+        .at("/")
+        .get(guard("environment:read", list_environment));
+    environment
+        .at("/add")
+        .post(guard("environment:write", add_environment));
+    environment
+        .at("/:id")
+        .get(guard("environment:read", get_environment))
+        .put(guard("environment:write", update_environment))
+        .delete(guard("environment:write", delete_environment));
+}
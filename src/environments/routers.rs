@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use mongodb::bson::{doc, from_document, to_bson, Document};
+use tide::Request;
+use validator::Validate;
+
+use super::schema::{AddEnvironment, GetEnvironment, ResEnvironment};
+use crate::db::Options;
+use crate::middleware::CurrentUser;
+use crate::models::{Environment, ENVIRONMENT};
+use crate::utils::*;
+use crate::State;
+
+fn to_res_environment(data: Document) -> Option<ResEnvironment> {
+    let id = data.get_object_id("_id").ok()?.to_hex();
+    let environment = from_document::<Environment>(data).ok()?;
+    Some(ResEnvironment::new(id, environment))
+}
+
+pub(crate) async fn add_environment(mut req: Request<State>) -> tide::Result {
+    let data: AddEnvironment = match req.body_json().await {
+        Ok(data) => data,
+        Err(error) => {
+            return Responser::new(Some(format!("{}", error)), &status::BAD_REQUEST).to_result()
+        }
+    };
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+
+    let project_id = req.param::<String>("pid")?;
+
+    let environment = match data.into_environment(project_id, user_id, &HashMap::new()) {
+        Some(environment) => environment,
+        None => return Responser::new(Some("未配置 secret_key"), &status::SYS_ERROR).to_result(),
+    };
+    let mut opt = Options::default();
+    opt.set_collect(&ENVIRONMENT);
+    let id = req.state().mongo.insert_one(opt, &environment).await?;
+    Responser::new(Some(ResEnvironment::new(id, environment)), &status::OK).to_result()
+}
+
+pub(crate) async fn get_environment(req: Request<State>) -> tide::Result {
+    crud::get_one(&req, &ENVIRONMENT, to_res_environment, "环境不存在").await
+}
+
+pub(crate) async fn list_environment(req: Request<State>) -> tide::Result {
+    let filter: GetEnvironment = match req.query() {
+        Ok(res) => res,
+        Err(e) => return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result(),
+    };
+
+    let mut condition = doc! { "project_id": req.param::<String>("pid")? };
+    if let Some(name) = filter.name {
+        condition.insert("name", name);
+    }
+
+    crud::list(&req, &ENVIRONMENT, condition, &filter.page, to_res_environment).await
+}
+
+pub(crate) async fn update_environment(mut req: Request<State>) -> tide::Result {
+    let data: AddEnvironment = match req.body_json().await {
+        Ok(data) => data,
+        Err(error) => {
+            return Responser::new(Some(format!("{}", error)), &status::BAD_REQUEST).to_result()
+        }
+    };
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let filter = match crud::project_filter(&req)? {
+        Ok(filter) => filter,
+        Err(e) => return Responser::new(Some(e), &status::BAD_REQUEST).to_result(),
+    };
+
+    // 密文变量没有重新提交时沿用原来的值
+    let mut opt = Options::default();
+    opt.find_one_opt(&ENVIRONMENT, Some(filter));
+    let old = match req.state().mongo.find(opt).await?.pop() {
+        Some(d) => from_document::<Environment>(d)?,
+        None => return Responser::new(Some("环境不存在"), &status::BAD_REQUEST).to_result(),
+    };
+
+    let project_id = req.param::<String>("pid")?;
+    let environment = match data.into_environment(project_id, user_id, &old.secrets) {
+        Some(environment) => environment,
+        None => return Responser::new(Some("未配置 secret_key"), &status::SYS_ERROR).to_result(),
+    };
+    let set = doc! {
+        "name": &environment.name,
+        "base_url": &environment.base_url,
+        "variables": to_bson(&environment.variables)?,
+        "headers": to_bson(&environment.headers)?,
+        "secrets": to_bson(&environment.secrets)?,
+    };
+    crud::update_one(&req, &ENVIRONMENT, set, "环境不存在").await
+}
+
+pub(crate) async fn delete_environment(req: Request<State>) -> tide::Result {
+    crud::delete_one(&req, &ENVIRONMENT, "环境不存在").await
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use chrono::prelude::Local;
+use serde_json::Value;
+use validator::Validate;
+
+use crate::models::{Environment, SECRET_MASK};
+use crate::utils::{encrypt, Page};
+
+#[derive(Deserialize, Validate)]
+pub(crate) struct AddEnvironment {
+    #[validate(length(min = 1, message = "name can not be empty"))]
+    pub(crate) name: String,
+    #[validate(url(message = "base_url error"))]
+    pub(crate) base_url: String,
+    #[serde(default)]
+    pub(crate) variables: HashMap<String, Value>,
+    #[serde(default)]
+    pub(crate) headers: HashMap<String, String>,
+    #[serde(default)]
+    pub(crate) secrets: HashMap<String, String>,
+}
+
+impl AddEnvironment {
+    // old 为修改前保存的 secrets，值为 SECRET_MASK 时沿用旧值；未配置 secret_key 时返回 None
+    pub(crate) fn into_environment(
+        self,
+        project_id: String,
+        user_id: String,
+        old: &HashMap<String, String>,
+    ) -> Option<Environment> {
+        let mut secrets = HashMap::new();
+        for (k, v) in self.secrets {
+            if v == SECRET_MASK {
+                if let Some(o) = old.get(&k) {
+                    secrets.insert(k, o.clone());
+                }
+            } else {
+                secrets.insert(k, encrypt(&v)?);
+            }
+        }
+        Some(Environment {
+            name: self.name,
+            base_url: self.base_url,
+            variables: self.variables,
+            headers: self.headers,
+            secrets,
+            project_id,
+            user_id,
+            create_at: Local::now(),
+            update_at: None,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub(crate) struct GetEnvironment {
+    pub(crate) name: Option<String>,
+    pub(crate) page: Page,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ResEnvironment {
+    pub(crate) id: String,
+    #[serde(flatten)]
+    pub(crate) environment: Environment,
+}
+
+impl ResEnvironment {
+    pub(crate) fn new(id: String, mut environment: Environment) -> Self {
+        for v in environment.secrets.values_mut() {
+            *v = String::from(SECRET_MASK);
+        }
+        Self { id, environment }
+    }
+}
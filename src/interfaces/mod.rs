@@ -1,13 +1,34 @@
+mod openapi;
 mod routers;
 mod schema;
+pub(crate) mod validation;
 
 use tide::Server;
 
 use crate::State;
-use routers::add_interface;
-use crate::middleware::LoginMiddleware;
+use routers::{
+    add_interface, delete_interface, export_openapi, get_interface, import_openapi,
+    list_interface, update_interface, validate_payload,
+};
+use crate::middleware::{guard, LoginMiddleware, ProjectMiddleware};
 
 pub(crate) fn interface_router(app: &mut Server<State>) {
-    let mut interface = app.with(LoginMiddleware).at("/interface");
-    interface.at("/add").post(add_interface);
+    let mut interface = app.at("/project/:pid/interface");
+    interface.with(LoginMiddleware).with(ProjectMiddleware);
+    interface.at("/").get(guard("interface:read", list_interface));
+    interface.at("/add").post(guard("interface:write", add_interface));
+    interface
+        .at("/import/openapi")
+        .post(guard("interface:write", import_openapi));
+    interface
+        .at("/export/openapi")
+        .get(guard("interface:read", export_openapi));
+    interface
+        .at("/:id")
+        .get(guard("interface:read", get_interface))
+        .put(guard("interface:write", update_interface))
+        .delete(guard("interface:write", delete_interface));
+    interface
+        .at("/:id/validate")
+        .post(guard("interface:read", validate_payload));
 }
@@ -0,0 +1,531 @@
+use chrono::prelude::Local;
+use serde_json::{json, Map, Value};
+
+use crate::models::{Field, Interface};
+
+const METHODS: [&str; 7] = ["get", "put", "post", "delete", "options", "head", "patch"];
+// 嵌套 schema 的最大展开层数，防止自引用的 schema 无限递归
+const MAX_DEPTH: usize = 8;
+
+// 解析 JSON 或 YAML 格式的文档
+pub(crate) fn parse(text: &str) -> Result<Value, String> {
+    match serde_json::from_str::<Value>(text) {
+        Ok(v) => Ok(v),
+        Err(_) => serde_yaml::from_str::<Value>(text).map_err(|e| e.to_string()),
+    }
+}
+
+// 把 OpenAPI 3 / Swagger 2 文档转换为 Interface
+pub(crate) fn import(
+    spec: &Value,
+    project_id: &str,
+    user_id: &str,
+) -> Result<Vec<Interface>, String> {
+    let swagger = match (spec.get("openapi"), spec.get("swagger")) {
+        (Some(Value::String(v)), _) if v.starts_with('3') => false,
+        (_, Some(Value::String(v))) if v.starts_with('2') => true,
+        _ => return Err(String::from("only support OpenAPI 3 or Swagger 2")),
+    };
+    let base_path = match spec.get("basePath").and_then(Value::as_str) {
+        Some(p) if swagger => p.trim_end_matches('/'),
+        _ => "",
+    };
+    let paths = match spec.get("paths").and_then(Value::as_object) {
+        Some(paths) => paths,
+        None => return Err(String::from("paths not found")),
+    };
+
+    let mut res = Vec::new();
+    for (path, item) in paths.iter() {
+        let item = resolve(spec, item);
+        let common = item.get("parameters").and_then(Value::as_array);
+        for method in METHODS.iter() {
+            let op = match item.get(*method) {
+                Some(op) => resolve(spec, op),
+                None => continue,
+            };
+            let mut param = Vec::new();
+            let mut data = Vec::new();
+            let parameters = common
+                .into_iter()
+                .flatten()
+                .chain(op.get("parameters").and_then(Value::as_array).into_iter().flatten());
+            for p in parameters {
+                let p = resolve(spec, p);
+                let name = p.get("name").and_then(Value::as_str).unwrap_or("");
+                let required = p.get("required").and_then(Value::as_bool).unwrap_or(false);
+                let schema = match p.get("schema") {
+                    Some(s) => resolve(spec, s),
+                    None => p,
+                };
+                match p.get("in").and_then(Value::as_str) {
+                    Some("query") => upsert(&mut param, to_field(spec, name, schema, required, 0)),
+                    Some("formData") => upsert(&mut data, to_field(spec, name, schema, required, 0)),
+                    Some("body") => {
+                        for f in schema_fields(spec, schema, 0) {
+                            upsert(&mut data, f);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(body) = op.get("requestBody") {
+                let content = resolve(spec, body).get("content").and_then(Value::as_object);
+                if let Some(content) = content {
+                    let media = [
+                        "application/json",
+                        "application/x-www-form-urlencoded",
+                        "multipart/form-data",
+                    ]
+                    .iter()
+                    .find_map(|m| content.get(*m))
+                    .or_else(|| content.values().next());
+                    if let Some(schema) = media.and_then(|m| m.get("schema")) {
+                        for f in schema_fields(spec, schema, 0) {
+                            upsert(&mut data, f);
+                        }
+                    }
+                }
+            }
+
+            let (response, example) = import_response(spec, op);
+
+            let description = ["summary", "description", "operationId"]
+                .iter()
+                .find_map(|k| op.get(*k).and_then(Value::as_str))
+                .unwrap_or("");
+            let module = op
+                .get("tags")
+                .and_then(|t| t.get(0))
+                .and_then(Value::as_str)
+                .unwrap_or("default");
+            res.push(Interface {
+                url: format!("{}{}", base_path, path.replace('{', "{{").replace('}', "}}")),
+                description: description.to_string(),
+                module: module.to_string(),
+                method: method.to_uppercase(),
+                data,
+                param,
+                response,
+                example,
+                project_id: project_id.to_string(),
+                user_id: user_id.to_string(),
+                create_at: Local::now(),
+                update_at: None,
+            });
+        }
+    }
+    Ok(res)
+}
+
+// 取 2xx 响应的 schema 和示例，兼容 OpenAPI 3 的 content 与 Swagger 2 的 schema
+fn import_response(spec: &Value, op: &Value) -> (Vec<Field>, Option<Value>) {
+    let responses = match op.get("responses").and_then(Value::as_object) {
+        Some(r) => r,
+        None => return (Vec::new(), None),
+    };
+    let response = match ["200", "201", "default"].iter().find_map(|c| responses.get(*c)) {
+        Some(r) => resolve(spec, r),
+        None => return (Vec::new(), None),
+    };
+    let media = response
+        .get("content")
+        .and_then(Value::as_object)
+        .and_then(|c| c.get("application/json").or_else(|| c.values().next()))
+        .unwrap_or(response);
+    let schema = media.get("schema").map(|s| resolve(spec, s));
+    let fields = schema
+        .map(|s| schema_fields(spec, s, 0))
+        .unwrap_or_default();
+    let example = media
+        .get("example")
+        .or_else(|| response.get("examples").and_then(|e| e.get("application/json")))
+        .or_else(|| schema.and_then(|s| s.get("example")))
+        .cloned();
+    (fields, example)
+}
+
+// 解析 $ref，只支持本文档内的引用
+fn resolve<'a>(spec: &'a Value, value: &'a Value) -> &'a Value {
+    let mut current = value;
+    // 防止循环引用
+    for _ in 0..16 {
+        let target = match current.get("$ref").and_then(Value::as_str) {
+            Some(r) => r,
+            None => return current,
+        };
+        let pointer = target.trim_start_matches('#');
+        current = match spec.pointer(pointer) {
+            Some(v) => v,
+            None => return current,
+        };
+    }
+    current
+}
+
+// 同名字段以后出现的为准
+fn upsert(fields: &mut Vec<Field>, field: Field) {
+    match fields.iter_mut().find(|f| f.name == field.name) {
+        Some(f) => *f = field,
+        None => fields.push(field),
+    }
+}
+
+// 对象 schema 的每个属性转为一个 Field
+fn schema_fields(spec: &Value, schema: &Value, depth: usize) -> Vec<Field> {
+    if depth >= MAX_DEPTH {
+        return Vec::new();
+    }
+    let schema = resolve(spec, schema);
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    let mut collect = |s: &Value| {
+        if let Some(props) = s.get("properties").and_then(Value::as_object) {
+            properties.extend(props.clone());
+        }
+        if let Some(req) = s.get("required").and_then(Value::as_array) {
+            required.extend(req.iter().filter_map(Value::as_str).map(String::from));
+        }
+    };
+    collect(schema);
+    if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+        for s in all {
+            collect(resolve(spec, s));
+        }
+    }
+
+    properties
+        .iter()
+        .map(|(name, s)| {
+            let required = required.contains(name);
+            to_field(spec, name, resolve(spec, s), required, depth + 1)
+        })
+        .collect()
+}
+
+fn to_usize(schema: &Value, key: &str) -> Option<usize> {
+    let v = schema.get(key)?;
+    v.as_u64()
+        .map(|n| n as usize)
+        .or_else(|| v.as_f64().filter(|n| *n >= 0.0).map(|n| n as usize))
+}
+
+fn to_field(spec: &Value, name: &str, schema: &Value, required: bool, depth: usize) -> Field {
+    let data_type = match schema.get("type").and_then(Value::as_str) {
+        Some(t) => t,
+        None if schema.get("properties").is_some() => "object",
+        None => "string",
+    };
+    let (length_min, length_max) = match data_type {
+        "array" => (to_usize(schema, "minItems"), to_usize(schema, "maxItems")),
+        _ => (to_usize(schema, "minLength"), to_usize(schema, "maxLength")),
+    };
+    Field {
+        name: name.to_string(),
+        required,
+        data_type: data_type.to_string(),
+        max: to_usize(schema, "maximum"),
+        min: to_usize(schema, "minimum"),
+        length_min,
+        length_max,
+        fields: match data_type {
+            "object" => schema_fields(spec, schema, depth),
+            _ => Vec::new(),
+        },
+        items: match schema.get("items") {
+            Some(items) if data_type == "array" && depth < MAX_DEPTH => {
+                Some(Box::new(to_field(spec, "", resolve(spec, items), false, depth + 1)))
+            }
+            _ => None,
+        },
+    }
+}
+
+// 导出为 OpenAPI 3 文档，module 作为 tag
+pub(crate) fn export(interfaces: &[Interface], title: &str) -> Value {
+    let mut tags: Vec<&str> = interfaces.iter().map(|i| i.module.as_str()).collect();
+    tags.sort();
+    tags.dedup();
+
+    let mut paths = Map::new();
+    for interface in interfaces {
+        let (path, path_params) = export_path(&interface.url);
+        let mut parameters: Vec<Value> = path_params
+            .iter()
+            .map(|name| json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } }))
+            .collect();
+        parameters.extend(interface.param.iter().map(|f| {
+            json!({ "name": f.name, "in": "query", "required": f.required, "schema": to_schema(f) })
+        }));
+
+        let mut op = json!({
+            "tags": [interface.module],
+            "summary": interface.description,
+            "responses": { "200": { "description": "success" } },
+        });
+        if !interface.response.is_empty() || interface.example.is_some() {
+            let mut media = json!({ "schema": object_schema(&interface.response) });
+            if let Some(example) = &interface.example {
+                media["example"] = example.clone();
+            }
+            op["responses"]["200"]["content"] = json!({ "application/json": media });
+        }
+        if !parameters.is_empty() {
+            op["parameters"] = Value::Array(parameters);
+        }
+        if !interface.data.is_empty() {
+            op["requestBody"] = json!({
+                "required": interface.data.iter().any(|f| f.required),
+                "content": { "application/json": { "schema": object_schema(&interface.data) } },
+            });
+        }
+
+        let item = paths
+            .entry(path)
+            .or_insert_with(|| Value::Object(Map::new()));
+        item[interface.method.to_lowercase()] = op;
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": { "title": title, "version": "1.0.0" },
+        "tags": tags.iter().map(|t| json!({ "name": t })).collect::<Vec<Value>>(),
+        "paths": paths,
+    })
+}
+
+// /user/{{id}} -> (/user/{id}, [id])
+fn export_path(url: &str) -> (String, Vec<String>) {
+    let mut path = String::new();
+    let mut params = Vec::new();
+    let mut rest = url;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+        let name = rest[start + 2..end].trim();
+        path.push_str(&rest[..start]);
+        path.push_str(&format!("{{{}}}", name));
+        params.push(name.to_string());
+        rest = &rest[end + 2..];
+    }
+    path.push_str(rest);
+    (path, params)
+}
+
+fn object_schema(fields: &[Field]) -> Value {
+    let properties: Map<String, Value> = fields
+        .iter()
+        .map(|f| (f.name.clone(), to_schema(f)))
+        .collect();
+    let required: Vec<&str> = fields
+        .iter()
+        .filter(|f| f.required)
+        .map(|f| f.name.as_str())
+        .collect();
+    let mut schema = json!({ "type": "object", "properties": properties });
+    if !required.is_empty() {
+        schema["required"] = json!(required);
+    }
+    schema
+}
+
+fn to_schema(field: &Field) -> Value {
+    let data_type = field.schema_type();
+    let mut schema = match data_type {
+        "object" if !field.fields.is_empty() => object_schema(&field.fields),
+        _ => json!({ "type": data_type }),
+    };
+    if let Some(items) = &field.items {
+        schema["items"] = to_schema(items);
+    }
+    let (min_len, max_len) = match data_type {
+        "array" => ("minItems", "maxItems"),
+        _ => ("minLength", "maxLength"),
+    };
+    if let Some(v) = field.min {
+        schema["minimum"] = json!(v);
+    }
+    if let Some(v) = field.max {
+        schema["maximum"] = json!(v);
+    }
+    if let Some(v) = field.length_min {
+        schema[min_len] = json!(v);
+    }
+    if let Some(v) = field.length_max {
+        schema[max_len] = json!(v);
+    }
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export, import, parse};
+
+    const OPENAPI: &str = r##"
+openapi: 3.0.0
+info:
+  title: test
+  version: "1"
+paths:
+  /user/{id}:
+    parameters:
+      - name: id
+        in: path
+        required: true
+        schema:
+          type: string
+    get:
+      tags: [user]
+      summary: get user
+      parameters:
+        - $ref: '#/components/parameters/Page'
+      responses:
+        "200":
+          description: ok
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/User'
+              example: { email: a@qq.com }
+    put:
+      tags: [user]
+      requestBody:
+        content:
+          application/json:
+            schema:
+              $ref: '#/components/schemas/User'
+components:
+  parameters:
+    Page:
+      name: page
+      in: query
+      schema:
+        type: integer
+        minimum: 1
+        maximum: 100
+  schemas:
+    User:
+      type: object
+      required: [email]
+      properties:
+        email:
+          type: string
+          minLength: 3
+          maxLength: 64
+        tags:
+          type: array
+          maxItems: 5
+          items:
+            type: string
+        address:
+          type: object
+          properties:
+            city:
+              type: string
+"##;
+
+    const SWAGGER: &str = r##"{
+  "swagger": "2.0",
+  "basePath": "/api/v1/",
+  "paths": {
+    "/auth/login": {
+      "post": {
+        "tags": ["auth"],
+        "description": "login",
+        "parameters": [
+          { "name": "body", "in": "body", "schema": { "$ref": "#/definitions/Login" } }
+        ]
+      }
+    }
+  },
+  "definitions": {
+    "Login": {
+      "properties": {
+        "email": { "type": "string" },
+        "password": { "type": "string", "minLength": 6 }
+      },
+      "required": ["email", "password"]
+    }
+  }
+}"##;
+
+    #[test]
+    fn test_import_openapi() {
+        let spec = parse(OPENAPI).unwrap();
+        let mut res = import(&spec, "pid", "uid").unwrap();
+        res.sort_by(|a, b| a.method.cmp(&b.method));
+        assert_eq!(res.len(), 2);
+
+        let get = &res[0];
+        assert_eq!(get.method, "GET");
+        assert_eq!(get.url, "/user/{{id}}");
+        assert_eq!(get.module, "user");
+        assert_eq!((get.project_id.as_str(), get.user_id.as_str()), ("pid", "uid"));
+        assert_eq!(get.description, "get user");
+        assert_eq!(get.param.len(), 1);
+        assert_eq!(get.param[0].name, "page");
+        assert_eq!(get.param[0].data_type, "integer");
+        assert_eq!((get.param[0].min, get.param[0].max), (Some(1), Some(100)));
+        assert_eq!(get.response.len(), 3);
+        assert_eq!(get.example, Some(serde_json::json!({ "email": "a@qq.com" })));
+
+        let put = &res[1];
+        assert_eq!(put.method, "PUT");
+        let email = put.data.iter().find(|f| f.name == "email").unwrap();
+        assert!(email.required);
+        assert_eq!((email.length_min, email.length_max), (Some(3), Some(64)));
+        let tags = put.data.iter().find(|f| f.name == "tags").unwrap();
+        assert!(!tags.required);
+        assert_eq!(tags.length_max, Some(5));
+        assert_eq!(tags.items.as_ref().unwrap().data_type, "string");
+        let address = put.data.iter().find(|f| f.name == "address").unwrap();
+        assert_eq!(address.fields[0].name, "city");
+    }
+
+    #[test]
+    fn test_import_swagger() {
+        let spec = parse(SWAGGER).unwrap();
+        let res = import(&spec, "pid", "uid").unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].url, "/api/v1/auth/login");
+        assert_eq!(res[0].module, "auth");
+        assert_eq!(res[0].data.len(), 2);
+        assert!(res[0].data.iter().all(|f| f.required));
+
+        assert!(import(&parse("{\"info\": {}}").unwrap(), "pid", "uid").is_err());
+    }
+
+    #[test]
+    fn test_export() {
+        let spec = parse(OPENAPI).unwrap();
+        let interfaces = import(&spec, "pid", "uid").unwrap();
+        let doc = export(&interfaces, "test");
+
+        assert_eq!(doc["tags"][0]["name"], "user");
+        let get = &doc["paths"]["/user/{id}"]["get"];
+        assert_eq!(get["parameters"][0]["in"], "path");
+        assert_eq!(get["parameters"][1]["schema"]["maximum"], 100);
+        let put = &doc["paths"]["/user/{id}"]["put"];
+        let schema = &put["requestBody"]["content"]["application/json"]["schema"];
+        assert_eq!(schema["required"][0], "email");
+        assert_eq!(schema["properties"]["tags"]["maxItems"], 5);
+        assert_eq!(schema["properties"]["tags"]["items"]["type"], "string");
+        assert_eq!(schema["properties"]["address"]["properties"]["city"]["type"], "string");
+
+        // 导出后再导入应得到相同的接口定义
+        let again = import(&doc, "pid", "uid").unwrap();
+        assert_eq!(again.len(), interfaces.len());
+        for interface in interfaces.iter() {
+            let other = again
+                .iter()
+                .find(|i| i.method == interface.method && i.url == interface.url)
+                .unwrap();
+            assert_eq!(other.param.len(), interface.param.len());
+            assert_eq!(other.data.len(), interface.data.len());
+            assert_eq!(other.response.len(), interface.response.len());
+            assert_eq!(other.example, interface.example);
+        }
+    }
+}
@@ -1,12 +1,75 @@
-use tide::{log, Request};
+use mongodb::bson::{doc, from_document, oid::ObjectId, to_bson, Document};
+use tide::http::{mime, Mime};
+use tide::{Request, Response, StatusCode};
 use validator::Validate;
 
-use crate::models::Interface;
+use super::schema::{
+    AddInterface, Conflict, GetExport, GetInterface, ResImport, ResInterface, ResValidate,
+    ValidatePayload,
+};
+use super::{openapi, validation};
+use crate::db::Options;
+use crate::middleware::CurrentUser;
+use crate::models::{Interface, INTERFACE};
 use crate::utils::*;
-use crate::{State, CONFIG};
+use crate::State;
+
+fn to_res_interface(data: Document) -> Option<ResInterface> {
+    let id = data.get_object_id("_id").ok()?.to_hex();
+    let interface = from_document::<Interface>(data).ok()?;
+    Some(ResInterface { id, interface })
+}
 
 pub(crate) async fn add_interface(mut req: Request<State>) -> tide::Result {
-    let data: Interface = match req.body_json().await {
+    let data: AddInterface = match req.body_json().await {
+        Ok(data) => data,
+        Err(error) => {
+            return Responser::new(Some(format!("{}", error)), &status::BAD_REQUEST).to_result()
+        }
+    };
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+
+    let project_id = req.param::<String>("pid")?;
+
+    let interface = data.into_interface(project_id, user_id);
+    let mut opt = Options::default();
+    opt.set_collect(&INTERFACE);
+    let id = req.state().mongo.insert_one(opt, &interface).await?;
+    Responser::new(Some(ResInterface { id, interface }), &status::OK).to_result()
+}
+
+pub(crate) async fn get_interface(req: Request<State>) -> tide::Result {
+    crud::get_one(&req, &INTERFACE, to_res_interface, "接口不存在").await
+}
+
+pub(crate) async fn list_interface(req: Request<State>) -> tide::Result {
+    let filter: GetInterface = match req.query() {
+        Ok(res) => res,
+        Err(e) => return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result(),
+    };
+
+    let mut condition = doc! { "project_id": req.param::<String>("pid")? };
+    if let Some(module) = filter.module {
+        condition.insert("module", module);
+    }
+    if let Some(method) = filter.method {
+        condition.insert("method", method.to_uppercase());
+    }
+    if let Some(url) = filter.url {
+        condition.insert("url", url);
+    }
+
+    crud::list(&req, &INTERFACE, condition, &filter.page, to_res_interface).await
+}
+
+pub(crate) async fn update_interface(mut req: Request<State>) -> tide::Result {
+    let data: AddInterface = match req.body_json().await {
         Ok(data) => data,
         Err(error) => {
             return Responser::new(Some(format!("{}", error)), &status::BAD_REQUEST).to_result()
@@ -15,6 +78,147 @@ pub(crate) async fn add_interface(mut req: Request<State>) -> tide::Result {
     if let Err(e) = data.validate() {
         return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
     }
-    log::error!("{:?}", data);
-    Responser::new(Some(data), &status::OK).to_result()
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+
+    let interface = data.into_interface(req.param::<String>("pid")?, user_id);
+    let set = doc! {
+        "url": &interface.url,
+        "description": &interface.description,
+        "module": &interface.module,
+        "method": &interface.method,
+        "data": to_bson(&interface.data)?,
+        "param": to_bson(&interface.param)?,
+        "response": to_bson(&interface.response)?,
+        "example": to_bson(&interface.example)?,
+    };
+    crud::update_one(&req, &INTERFACE, set, "接口不存在").await
+}
+
+pub(crate) async fn delete_interface(req: Request<State>) -> tide::Result {
+    crud::delete_one(&req, &INTERFACE, "接口不存在").await
+}
+
+pub(crate) async fn import_openapi(mut req: Request<State>) -> tide::Result {
+    let text = req.body_string().await?;
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let project_id = req.param::<String>("pid")?;
+    let spec = openapi::parse(&text);
+    let interfaces = match spec.and_then(|s| openapi::import(&s, &project_id, &user_id)) {
+        Ok(res) => res,
+        Err(e) => return Responser::new(Some(e), &status::BAD_REQUEST).to_result(),
+    };
+
+    // 同一项目中 url 和 method 相同的接口视为冲突，不做覆盖
+    let mongo = &req.state().mongo;
+    let mut res = ResImport {
+        imported: Vec::new(),
+        conflicts: Vec::new(),
+    };
+    for interface in interfaces {
+        let mut opt = Options::default();
+        opt.find_one_opt(
+            &INTERFACE,
+            Some(doc! {
+                "project_id": &project_id,
+                "url": &interface.url,
+                "method": &interface.method,
+            }),
+        );
+        if let Some(exist) = mongo.find(opt).await?.pop() {
+            res.conflicts.push(Conflict {
+                id: exist.get_object_id("_id")?.to_hex(),
+                url: interface.url,
+                method: interface.method,
+            });
+            continue;
+        }
+        let mut opt = Options::default();
+        opt.set_collect(&INTERFACE);
+        let id = mongo.insert_one(opt, &interface).await?;
+        res.imported.push(ResInterface { id, interface });
+    }
+    Responser::new(Some(res), &status::OK).to_result()
+}
+
+pub(crate) async fn export_openapi(req: Request<State>) -> tide::Result {
+    let filter: GetExport = match req.query() {
+        Ok(res) => res,
+        Err(e) => return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result(),
+    };
+
+    let mut condition = doc! { "project_id": req.param::<String>("pid")? };
+    if let Some(module) = &filter.module {
+        condition.insert("module", module);
+    }
+    let opt = Options::new(
+        &INTERFACE,
+        Some(condition),
+        None,
+        None,
+        Some(doc! { "module": 1, "url": 1 }),
+        None,
+    );
+    let interfaces: Vec<Interface> = req
+        .state()
+        .mongo
+        .find(opt)
+        .await?
+        .into_iter()
+        .filter_map(|d| from_document::<Interface>(d).ok())
+        .collect();
+    let title = filter.module.as_deref().unwrap_or("tide-server-example");
+    let spec = openapi::export(&interfaces, title);
+
+    // 直接返回文档本身，方便导入其他工具
+    let mut res = Response::new(StatusCode::Ok);
+    match filter.format.as_deref().unwrap_or("json") {
+        "json" => {
+            res.set_body(serde_json::to_string_pretty(&spec)?);
+            res.set_content_type(mime::JSON);
+        }
+        "yaml" | "yml" => {
+            res.set_body(serde_yaml::to_string(&spec)?);
+            res.set_content_type("application/yaml".parse::<Mime>()?);
+        }
+        _ => {
+            return Responser::new(Some("format 只支持 json 或 yaml"), &status::BAD_REQUEST)
+                .to_result()
+        }
+    }
+    Ok(res)
+}
+
+pub(crate) async fn validate_payload(mut req: Request<State>) -> tide::Result {
+    let payload: ValidatePayload = match req.body_json().await {
+        Ok(data) => data,
+        Err(error) => {
+            return Responser::new(Some(format!("{}", error)), &status::BAD_REQUEST).to_result()
+        }
+    };
+    let project_id = req.param::<String>("pid")?;
+    let id = req.param::<String>("id")?;
+    let oid = match ObjectId::with_string(&id) {
+        Ok(oid) => oid,
+        Err(e) => return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result(),
+    };
+
+    let mut opt = Options::default();
+    opt.find_one_opt(&INTERFACE, Some(doc! { "_id": oid, "project_id": project_id }));
+    let interface = match req.state().mongo.find(opt).await?.pop() {
+        Some(data) => from_document::<Interface>(data)?,
+        None => return Responser::new(Some("接口不存在"), &status::BAD_REQUEST).to_result(),
+    };
+
+    let violations = validation::validate(&interface, &payload.param, &payload.data);
+    let res = ResValidate {
+        passed: violations.is_empty(),
+        violations,
+    };
+    Responser::new(Some(res), &status::OK).to_result()
 }
@@ -0,0 +1,103 @@
+use chrono::prelude::Local;
+use serde_json::{Map, Value};
+use validator::{Validate, ValidationError};
+
+use super::validation::Violation;
+use crate::models::{Field, Interface};
+use crate::utils::Page;
+
+const METHODS: [&str; 7] = ["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS"];
+
+fn validate_method(method: &str) -> Result<(), ValidationError> {
+    if METHODS.contains(&method.to_uppercase().as_str()) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("method not support"))
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub(crate) struct AddInterface {
+    #[validate(length(min = 1, message = "url can not be empty"))]
+    pub(crate) url: String,
+    pub(crate) description: String,
+    #[validate(length(min = 1, message = "module can not be empty"))]
+    pub(crate) module: String,
+    #[validate(custom = "validate_method")]
+    pub(crate) method: String,
+    #[validate]
+    pub(crate) data: Vec<Field>,
+    #[validate]
+    pub(crate) param: Vec<Field>,
+    #[serde(default)]
+    pub(crate) response: Vec<Field>,
+    #[serde(default)]
+    pub(crate) example: Option<Value>,
+}
+
+impl AddInterface {
+    pub(crate) fn into_interface(self, project_id: String, user_id: String) -> Interface {
+        Interface {
+            url: self.url,
+            description: self.description,
+            module: self.module,
+            method: self.method.to_uppercase(),
+            data: self.data,
+            param: self.param,
+            response: self.response,
+            example: self.example,
+            project_id,
+            user_id,
+            create_at: Local::now(),
+            update_at: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub(crate) struct GetInterface {
+    pub(crate) module: Option<String>,
+    pub(crate) method: Option<String>,
+    pub(crate) url: Option<String>,
+    pub(crate) page: Page,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct GetExport {
+    pub(crate) module: Option<String>,
+    pub(crate) format: Option<String>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ResInterface {
+    pub(crate) id: String,
+    #[serde(flatten)]
+    pub(crate) interface: Interface,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Conflict {
+    pub(crate) id: String,
+    pub(crate) url: String,
+    pub(crate) method: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ResImport {
+    pub(crate) imported: Vec<ResInterface>,
+    pub(crate) conflicts: Vec<Conflict>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ValidatePayload {
+    #[serde(default)]
+    pub(crate) param: Map<String, Value>,
+    #[serde(default)]
+    pub(crate) data: Value,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ResValidate {
+    pub(crate) passed: bool,
+    pub(crate) violations: Vec<Violation>,
+}
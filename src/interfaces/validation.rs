@@ -0,0 +1,245 @@
+use serde_json::{Map, Value};
+
+use crate::models::{Field, Interface};
+
+#[derive(Serialize, Debug)]
+pub(crate) struct Violation {
+    // query 或 body
+    pub(crate) location: &'static str,
+    // 字段路径，如 user.tags[0]
+    pub(crate) field: String,
+    // missing / type / range / length
+    pub(crate) kind: &'static str,
+    pub(crate) message: String,
+}
+
+// 按接口的 Field 定义校验请求参数，返回所有不符合的地方
+pub(crate) fn validate(
+    interface: &Interface,
+    query: &Map<String, Value>,
+    body: &Value,
+) -> Vec<Violation> {
+    let mut res = Vec::new();
+
+    // query 的值都是字符串，先按字段类型转换
+    let query: Map<String, Value> = query
+        .iter()
+        .map(|(k, v)| {
+            let v = match interface.param.iter().find(|f| &f.name == k) {
+                Some(field) => coerce(field, v),
+                None => v.clone(),
+            };
+            (k.clone(), v)
+        })
+        .collect();
+    check_object(&interface.param, &query, "", "query", &mut res);
+
+    match body {
+        Value::Object(map) => check_object(&interface.data, map, "", "body", &mut res),
+        Value::Null => check_object(&interface.data, &Map::new(), "", "body", &mut res),
+        _ if interface.data.is_empty() => {}
+        _ => res.push(Violation {
+            location: "body",
+            field: String::new(),
+            kind: "type",
+            message: String::from("body must be an object"),
+        }),
+    }
+    res
+}
+
+fn coerce(field: &Field, value: &Value) -> Value {
+    let text = match value {
+        Value::String(s) => s,
+        _ => return value.clone(),
+    };
+    let parsed = match field.schema_type() {
+        "integer" => text.parse::<i64>().ok().map(Value::from),
+        "number" => text.parse::<f64>().ok().map(Value::from),
+        "boolean" => text.parse::<bool>().ok().map(Value::from),
+        "array" => Some(Value::Array(
+            text.split(',').map(|s| Value::String(s.to_string())).collect(),
+        )),
+        "object" => serde_json::from_str::<Value>(text).ok(),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| value.clone())
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
+
+fn check_object(
+    fields: &[Field],
+    map: &Map<String, Value>,
+    prefix: &str,
+    location: &'static str,
+    res: &mut Vec<Violation>,
+) {
+    for field in fields {
+        let path = join(prefix, &field.name);
+        match map.get(&field.name) {
+            None | Some(Value::Null) => {
+                if field.required {
+                    res.push(Violation {
+                        location,
+                        message: format!("{} is required", path),
+                        field: path,
+                        kind: "missing",
+                    });
+                }
+            }
+            Some(value) => check_field(field, value, path, location, res),
+        }
+    }
+}
+
+fn check_field(
+    field: &Field,
+    value: &Value,
+    path: String,
+    location: &'static str,
+    res: &mut Vec<Violation>,
+) {
+    let data_type = field.schema_type();
+    let matched = match data_type {
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        _ => value.is_string(),
+    };
+    if !matched {
+        res.push(Violation {
+            location,
+            message: format!("{} should be {}", path, data_type),
+            field: path,
+            kind: "type",
+        });
+        return;
+    }
+
+    if let Some(n) = value.as_f64() {
+        let below = field.min.map(|min| n < min as f64).unwrap_or(false);
+        let above = field.max.map(|max| n > max as f64).unwrap_or(false);
+        if below || above {
+            res.push(Violation {
+                location,
+                message: format!("{} out of range [{:?}, {:?}]", path, field.min, field.max),
+                field: path.clone(),
+                kind: "range",
+            });
+        }
+    }
+
+    let len = match value {
+        Value::String(s) => Some(s.chars().count()),
+        Value::Array(a) => Some(a.len()),
+        _ => None,
+    };
+    if let Some(len) = len {
+        let short = field.length_min.map(|min| len < min).unwrap_or(false);
+        let long = field.length_max.map(|max| len > max).unwrap_or(false);
+        if short || long {
+            res.push(Violation {
+                location,
+                message: format!(
+                    "{} length {} not in [{:?}, {:?}]",
+                    path, len, field.length_min, field.length_max
+                ),
+                field: path.clone(),
+                kind: "length",
+            });
+        }
+    }
+
+    match value {
+        Value::Object(map) => check_object(&field.fields, map, &path, location, res),
+        Value::Array(list) => {
+            if let Some(items) = &field.items {
+                for (i, item) in list.iter().enumerate() {
+                    check_field(items, item, format!("{}[{}]", path, i), location, res);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::Local;
+    use serde_json::{from_value, json, Map};
+
+    use super::validate;
+    use crate::models::{Field, Interface};
+
+    #[test]
+    fn test_validate() {
+        let fields = |v| from_value::<Vec<Field>>(v).unwrap();
+        let interface = Interface {
+            url: String::from("/user"),
+            description: String::new(),
+            module: String::from("user"),
+            method: String::from("POST"),
+            data: fields(json!([
+                { "name": "email", "required": true, "data_type": "string",
+                  "length_min": 3, "length_max": 8 },
+                { "name": "age", "required": false, "data_type": "integer", "min": 1, "max": 120 },
+                { "name": "address", "required": true, "data_type": "object", "fields": [
+                    { "name": "city", "required": true, "data_type": "string" }
+                ]},
+                { "name": "tags", "required": false, "data_type": "array", "length_max": 2,
+                  "items": { "name": "", "required": false, "data_type": "string" } },
+            ])),
+            param: fields(json!([
+                { "name": "page", "required": true, "data_type": "integer", "min": 1 },
+            ])),
+            response: Vec::new(),
+            example: None,
+            project_id: String::new(),
+            user_id: String::new(),
+            create_at: Local::now(),
+            update_at: None,
+        };
+
+        let mut query = Map::new();
+        query.insert(String::from("page"), json!("2"));
+        let body = json!({ "email": "a@qq.com", "address": { "city": "sz" }, "tags": ["a"] });
+        assert!(validate(&interface, &query, &body).is_empty());
+
+        query.insert(String::from("page"), json!("0"));
+        let body = json!({
+            "email": "abcdefghijk",
+            "age": "18",
+            "address": {},
+            "tags": ["a", 1, "c"],
+        });
+        let res: Vec<(String, &str)> = validate(&interface, &query, &body)
+            .into_iter()
+            .map(|v| (v.field, v.kind))
+            .collect();
+        let expected = vec![
+            ("page", "range"),
+            ("email", "length"),
+            ("age", "type"),
+            ("address.city", "missing"),
+            ("tags", "length"),
+            ("tags[1]", "type"),
+        ];
+        assert_eq!(res.len(), expected.len(), "{:?}", res);
+        for (field, kind) in expected {
+            assert!(res.contains(&(field.to_string(), kind)), "{} {}", field, kind);
+        }
+
+        let res = validate(&interface, &Map::new(), &json!([1]));
+        assert_eq!(res[0].field, "page");
+        assert_eq!(res[1].message, "body must be an object");
+    }
+}
@@ -7,12 +7,18 @@ use tide::utils::After;
 use tide::{log, Server};
 
 mod auth;
+mod cases;
 mod db;
+mod environments;
 mod interfaces;
 mod middleware;
+mod mock;
 mod models;
+mod projects;
+mod runner;
 mod setting;
 mod state;
+mod steps;
 mod users;
 mod utils;
 
@@ -34,9 +40,14 @@ async fn main() -> tide::Result<()> {
         let mut api = Server::with_state(state.clone());
         auth::auth_router(&mut api);
         users::user_router(&mut api);
+        projects::project_router(&mut api);
         interfaces::interface_router(&mut api);
+        steps::step_router(&mut api);
+        cases::case_router(&mut api);
+        environments::environment_router(&mut api);
         api
     });
+    mock::mock_router(&mut app);
     log::info!("app is running");
     app.listen(CONFIG.server.server.clone()).await?;
     Ok(())
@@ -1,40 +1,171 @@
-use redis::{AsyncCommands, Client};
+use chrono::prelude::Local;
+use mongodb::bson::{doc, from_document, oid::ObjectId, to_bson};
 use tide::{Middleware, Next, Request};
 
-use crate::db::Redis;
-use crate::CONFIG;
-use crate::utils::{status, Responser};
+use crate::db::Options;
+use crate::models::{scope_allows, ApiKey, Role, User, API_KEY, USER};
+use crate::utils::{password_verify, status, Responser, KEYSET};
+use crate::{State, CONFIG};
 
-pub struct LoginMiddleware {
-    conn: Client,
-}
+pub struct LoginMiddleware;
 
 #[derive(Serialize, Deserialize)]
 pub struct Token {
     pub token: String,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    // access token 有效期，单位秒
+    pub expires_in: usize,
+}
+
+// 当前登录的用户，由 LoginMiddleware 放入 request ext
+#[derive(Clone, Debug)]
+pub struct CurrentUser {
+    pub id: String,
+    pub username: String,
+    pub roles: Vec<Role>,
+    pub active: bool,
+    // 是否已开启两步验证
+    pub mfa: bool,
+    // 使用 API key 访问时为 key 的 scopes，session 登录时为 None
+    pub scopes: Option<Vec<String>>,
+}
+
+impl CurrentUser {
+    pub fn can(&self, permission: &str) -> bool {
+        self.in_scope(permission) && self.roles.iter().any(|r| r.allows(permission))
+    }
+
+    pub fn in_scope(&self, permission: &str) -> bool {
+        match &self.scopes {
+            Some(scopes) => scope_allows(scopes, permission),
+            None => true,
+        }
+    }
+}
+
+// 取 Authorization 中的 token，支持 Bearer 和直接传 token
+pub(crate) fn bearer(req: &Request<State>) -> Option<String> {
+    let value = req.header("Authorization")?.as_str().trim();
+    let token = match value.split_once(' ') {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => token.trim(),
+        Some(_) => return None,
+        None => value,
+    };
+    if token.is_empty() {
+        None
+    } else {
+        Some(token.to_string())
+    }
+}
+
+// session 登录的用户 id，API key 访问时为 None。
+// 修改密码、邮箱，管理 session、API key 和两步验证只能在 session 登录时进行，
+// 避免 key 泄露后被用来接管帐号
+pub(crate) fn session_owner(req: &Request<State>) -> Option<String> {
+    match req.ext::<CurrentUser>() {
+        Some(u) if u.scopes.is_none() => Some(u.id.clone()),
+        _ => None,
+    }
+}
+
 impl LoginMiddleware {
-    pub async fn new() -> tide::Result<Self> {
-        let red = Redis::new(&CONFIG.database.redis_url)?;
-        Ok(Self { conn: red.cli })
+    // 返回 token 所属的用户和 session family
+    async fn session(state: &State, token: &str) -> tide::Result<Option<(String, String)>> {
+        // JWT 模式只查黑名单
+        if CONFIG.jwt.enabled {
+            let claims = match KEYSET.decode(token) {
+                Ok(claims) => claims,
+                Err(_) => return Ok(None),
+            };
+            if state.redis.is_denied(&claims.jti).await? {
+                return Ok(None);
+            }
+            return Ok(Some((claims.sub, claims.sid)));
+        }
+        state.redis.access_session(token).await
+    }
+
+    // 返回 key 所属的用户和 scopes
+    async fn api_key(state: &State, key: &str) -> tide::Result<Option<(String, Vec<String>)>> {
+        let (prefix, secret) = match ApiKey::split(key) {
+            Some(res) => res,
+            None => return Ok(None),
+        };
+        let mut opt = Options::default();
+        opt.find_one_opt(&API_KEY, Some(doc! { "prefix": prefix }));
+        let data = match state.mongo.find(opt).await?.pop() {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        let oid = data.get_object_id("_id")?.clone();
+        let api_key: ApiKey = from_document(data)?;
+        if api_key.expired() || !password_verify(&api_key.hash, secret) {
+            return Ok(None);
+        }
+
+        let mut opt = Options::default();
+        opt.update_opt(&API_KEY, Some(doc! { "_id": oid }), None);
+        let update = doc! { "$set": { "last_used": to_bson(&Local::now())? } };
+        state.mongo.update(update, opt).await?;
+        Ok(Some((api_key.user_id, api_key.scopes)))
     }
 }
 
 #[tide::utils::async_trait]
-impl<State: Clone + Send + Sync + 'static> Middleware<State> for LoginMiddleware {
+impl Middleware<State> for LoginMiddleware {
     async fn handle(&self, mut request: Request<State>, next: Next<'_, State>) -> tide::Result {
-        match request.header("Authorization") {
-            Some(token) => {
-                let mut con = self.conn.clone().get_async_connection().await?;
-                let token_key = token.as_str();
-                let redis_token: String = con.get(token_key).await?;
-                let token = Token { token: redis_token };
-                request.set_ext(token);
-                let res = next.run(request).await;
-                Ok(res)
-            }
-            None => Responser::new(Some(""), &status::UNAUTH).to_result(),
+        // 有 X-Api-Key 时优先使用 API key
+        let api_key = request
+            .header("X-Api-Key")
+            .map(|h| h.as_str().trim().to_string());
+        let (id, scopes) = match (api_key, bearer(&request)) {
+            (Some(key), _) => match Self::api_key(request.state(), &key).await? {
+                Some((id, scopes)) => (id, Some(scopes)),
+                None => {
+                    return Responser::new(Some("API key 无效或已过期"), &status::UNAUTH)
+                        .to_result()
+                }
+            },
+            (None, Some(token)) => match Self::session(request.state(), &token).await? {
+                Some((id, family)) => {
+                    // JWT 模式下除黑名单外不访问 redis，最后活跃时间只在刷新 token 时更新
+                    if !CONFIG.jwt.enabled {
+                        request.state().redis.touch_session(&id, &family).await?;
+                    }
+                    (id, None)
+                }
+                None => return Responser::new(Some("登录已过期"), &status::UNAUTH).to_result(),
+            },
+            (None, None) => return Responser::new(Some("请先登录"), &status::UNAUTH).to_result(),
+        };
+        let oid = match ObjectId::with_string(&id) {
+            Ok(oid) => oid,
+            Err(_) => return Responser::new(Some("登录已过期"), &status::UNAUTH).to_result(),
+        };
+
+        let mut opt = Options::default();
+        opt.find_one_opt(&USER, Some(doc! { "_id": oid }));
+        let user: User = match request.state().mongo.find(opt).await?.pop() {
+            Some(data) => from_document(data)?,
+            None => return Responser::new(Some("帐号不存在"), &status::UNAUTH).to_result(),
+        };
+        if !user.active {
+            return Responser::new(Some("帐号未激活"), &status::UNAUTH).to_result();
         }
+
+        request.set_ext(CurrentUser {
+            id,
+            username: user.username,
+            roles: user.roles,
+            active: user.active,
+            mfa: user.mfa.map(|m| m.enabled).unwrap_or(false),
+            scopes,
+        });
+        Ok(next.run(request).await)
     }
 }
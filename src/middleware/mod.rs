@@ -1,3 +1,9 @@
 mod login_middleware;
+mod permission;
+mod project_middleware;
 
-pub(crate) use login_middleware::{LoginMiddleware, Token};
+pub(crate) use login_middleware::{
+    bearer, session_owner, CurrentUser, LoginMiddleware, Token, TokenPair,
+};
+pub(crate) use permission::guard;
+pub(crate) use project_middleware::{CurrentProject, ProjectMiddleware};
@@ -0,0 +1,42 @@
+use tide::{Endpoint, Request};
+
+use super::{CurrentProject, CurrentUser};
+use crate::utils::{status, Responser};
+use crate::State;
+
+// 只有管理员拥有的权限，要求帐号开启两步验证
+const MFA_PERMISSIONS: [&str; 1] = ["user:admin"];
+
+// 需要在 LoginMiddleware 之后使用，项目内的路由按项目角色判断
+pub struct Guard<E> {
+    permission: &'static str,
+    endpoint: E,
+}
+
+// 给路由声明所需权限，如 guard("interface:write", add_interface)
+pub fn guard<E: Endpoint<State>>(permission: &'static str, endpoint: E) -> Guard<E> {
+    Guard {
+        permission,
+        endpoint,
+    }
+}
+
+#[tide::utils::async_trait]
+impl<E: Endpoint<State>> Endpoint<State> for Guard<E> {
+    async fn call(&self, req: Request<State>) -> tide::Result {
+        let allowed = match (req.ext::<CurrentUser>(), req.ext::<CurrentProject>()) {
+            (None, _) => return Responser::new(Some("请先登录"), &status::UNAUTH).to_result(),
+            (Some(user), Some(project)) => {
+                user.in_scope(self.permission) && project.role.allows(self.permission)
+            }
+            (Some(user), None) if !user.mfa && MFA_PERMISSIONS.contains(&self.permission) => {
+                return Responser::new(Some("请先开启两步验证"), &status::FORBIDDEN).to_result()
+            }
+            (Some(user), None) => user.can(self.permission),
+        };
+        if !allowed {
+            return Responser::new(Some(self.permission), &status::FORBIDDEN).to_result();
+        }
+        self.endpoint.call(req).await
+    }
+}
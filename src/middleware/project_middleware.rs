@@ -0,0 +1,48 @@
+use mongodb::bson::{doc, from_document, oid::ObjectId};
+use tide::{Middleware, Next, Request};
+
+use super::CurrentUser;
+use crate::db::Options;
+use crate::models::{Project, Role, PROJECT};
+use crate::utils::{status, Responser};
+use crate::State;
+
+// 需要在 LoginMiddleware 之后使用，路由中必须有 :pid
+pub struct ProjectMiddleware;
+
+// 当前请求所在的项目及用户在项目中的角色
+#[derive(Clone, Debug)]
+pub struct CurrentProject {
+    pub id: String,
+    pub role: Role,
+}
+
+#[tide::utils::async_trait]
+impl Middleware<State> for ProjectMiddleware {
+    async fn handle(&self, mut request: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let user_id = match request.ext::<CurrentUser>() {
+            Some(u) => u.id.clone(),
+            None => return Responser::new(Some("请先登录"), &status::UNAUTH).to_result(),
+        };
+        let pid = request.param::<String>("pid")?;
+        let oid = match ObjectId::with_string(&pid) {
+            Ok(oid) => oid,
+            Err(e) => return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result(),
+        };
+
+        // 不是成员时与项目不存在返回相同的结果
+        let mut opt = Options::default();
+        opt.find_one_opt(&PROJECT, Some(doc! { "_id": oid, "members.user_id": &user_id }));
+        let project: Project = match request.state().mongo.find(opt).await?.pop() {
+            Some(data) => from_document(data)?,
+            None => return Responser::new(Some("项目不存在"), &status::BAD_REQUEST).to_result(),
+        };
+        let role = match project.role(&user_id) {
+            Some(role) => role,
+            None => return Responser::new(Some("项目不存在"), &status::BAD_REQUEST).to_result(),
+        };
+
+        request.set_ext(CurrentProject { id: pid, role });
+        Ok(next.run(request).await)
+    }
+}
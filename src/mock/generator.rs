@@ -0,0 +1,100 @@
+use chrono::prelude::Local;
+use serde_json::{json, Map, Value};
+
+use crate::models::Field;
+
+// 按字段类型生成示例数据，并满足范围和长度约束
+pub(crate) fn generate(fields: &[Field]) -> Value {
+    let map: Map<String, Value> = fields
+        .iter()
+        .map(|f| (f.name.clone(), generate_field(f)))
+        .collect();
+    Value::Object(map)
+}
+
+fn generate_field(field: &Field) -> Value {
+    match field.schema_type() {
+        "integer" => {
+            let n = field.min.unwrap_or(1);
+            json!(field.max.map_or(n, |max| n.min(max)))
+        }
+        "number" => {
+            let n = field.min.unwrap_or(1);
+            json!(field.max.map_or(n, |max| n.min(max)) as f64)
+        }
+        "boolean" => json!(true),
+        "object" => generate(&field.fields),
+        "array" => {
+            let n = field.length_min.unwrap_or(1);
+            let n = field.length_max.map_or(n, |max| n.min(max));
+            let item = match &field.items {
+                Some(items) => generate_field(items),
+                None => json!("item"),
+            };
+            Value::Array(vec![item; n])
+        }
+        _ => Value::String(generate_str(field)),
+    }
+}
+
+fn generate_str(field: &Field) -> String {
+    let name = field.name.to_lowercase();
+    let mut s = if name.contains("email") {
+        String::from("mock@example.com")
+    } else if name.contains("url") {
+        String::from("https://example.com")
+    } else if name.contains("time") || name.contains("date") {
+        Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+    } else if name.is_empty() {
+        String::from("string")
+    } else {
+        field.name.clone()
+    };
+
+    let len = s.chars().count();
+    if let Some(min) = field.length_min.filter(|min| len < *min) {
+        s.push_str(&"x".repeat(min - len));
+    }
+    if let Some(max) = field.length_max {
+        s = s.chars().take(max).collect();
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{from_value, json};
+
+    use super::generate;
+    use crate::models::Field;
+
+    #[test]
+    fn test_generate() {
+        let fields: Vec<Field> = from_value(json!([
+            { "name": "id", "required": true, "data_type": "integer", "min": 10, "max": 20 },
+            { "name": "email", "required": true, "data_type": "string" },
+            { "name": "code", "required": true, "data_type": "string",
+              "length_min": 6, "length_max": 6 },
+            { "name": "active", "required": true, "data_type": "bool" },
+            { "name": "profile", "required": true, "data_type": "object", "fields": [
+                { "name": "age", "required": false, "data_type": "integer", "max": 0 }
+            ]},
+            { "name": "tags", "required": true, "data_type": "array", "length_min": 2,
+              "items": { "name": "", "required": false, "data_type": "string" } },
+        ]))
+        .unwrap();
+
+        let res = generate(&fields);
+        assert_eq!(
+            res,
+            json!({
+                "id": 10,
+                "email": "mock@example.com",
+                "code": "codexx",
+                "active": true,
+                "profile": { "age": 0 },
+                "tags": ["string", "string"],
+            })
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use crate::models::Interface;
+
+// 按 url 匹配接口，{{x}} 可以匹配任意一段，字面量段越多越优先
+pub(crate) fn best_match<'a>(interfaces: &'a [Interface], path: &str) -> Option<&'a Interface> {
+    interfaces
+        .iter()
+        .filter_map(|i| score(&i.url, path).map(|s| (s, i)))
+        .max_by_key(|(s, _)| *s)
+        .map(|(_, i)| i)
+}
+
+fn score(pattern: &str, path: &str) -> Option<usize> {
+    let pattern: Vec<&str> = pattern.trim_matches('/').split('/').collect();
+    let path: Vec<&str> = path.trim_matches('/').split('/').collect();
+    if pattern.len() != path.len() {
+        return None;
+    }
+    let mut literal = 0;
+    for (p, s) in pattern.iter().zip(path.iter()) {
+        if p.starts_with("{{") && p.ends_with("}}") {
+            if s.is_empty() {
+                return None;
+            }
+        } else if p == s {
+            literal += 1;
+        } else {
+            return None;
+        }
+    }
+    Some(literal)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::Local;
+
+    use super::best_match;
+    use crate::models::Interface;
+
+    fn interface(url: &str) -> Interface {
+        Interface {
+            url: url.to_string(),
+            description: String::new(),
+            module: String::from("user"),
+            method: String::from("GET"),
+            data: Vec::new(),
+            param: Vec::new(),
+            response: Vec::new(),
+            example: None,
+            project_id: String::new(),
+            user_id: String::new(),
+            create_at: Local::now(),
+            update_at: None,
+        }
+    }
+
+    #[test]
+    fn test_best_match() {
+        let interfaces = vec![
+            interface("/user/{{id}}"),
+            interface("/user/me"),
+            interface("/user/{{id}}/tags"),
+        ];
+        let url = |path| best_match(&interfaces, path).map(|i| i.url.as_str());
+        assert_eq!(url("user/12"), Some("/user/{{id}}"));
+        assert_eq!(url("/user/me"), Some("/user/me"));
+        assert_eq!(url("user/12/tags"), Some("/user/{{id}}/tags"));
+        assert_eq!(url("user"), None);
+        assert_eq!(url("user/12/name"), None);
+    }
+}
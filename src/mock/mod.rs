@@ -0,0 +1,17 @@
+mod generator;
+mod matcher;
+mod routers;
+
+use tide::Server;
+
+use crate::middleware::{guard, LoginMiddleware, ProjectMiddleware};
+use crate::State;
+use routers::mock;
+
+// 前端联调用，:pid 为项目 id，只有项目成员可以访问
+pub(crate) fn mock_router(app: &mut Server<State>) {
+    app.at("/mock/:pid/*path")
+        .with(LoginMiddleware)
+        .with(ProjectMiddleware)
+        .all(guard("interface:read", mock));
+}
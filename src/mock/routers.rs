@@ -0,0 +1,65 @@
+use mongodb::bson::{doc, from_document};
+use serde_json::{Map, Value};
+use tide::{Request, Response, StatusCode};
+
+use super::generator::generate;
+use super::matcher::best_match;
+use crate::db::Options;
+use crate::interfaces::validation::validate;
+use crate::models::{Interface, INTERFACE};
+use crate::utils::*;
+use crate::State;
+
+pub(crate) async fn mock(mut req: Request<State>) -> tide::Result {
+    let project = req.param::<String>("pid")?;
+    let path = req.param::<String>("path")?;
+    let method = req.method().to_string();
+
+    let opt = Options::new(
+        &INTERFACE,
+        Some(doc! { "project_id": &project, "method": &method }),
+        None,
+        None,
+        None,
+        None,
+    );
+    let interfaces: Vec<Interface> = req
+        .state()
+        .mongo
+        .find(opt)
+        .await?
+        .into_iter()
+        .filter_map(|d| from_document::<Interface>(d).ok())
+        .collect();
+    let interface = match best_match(&interfaces, &path) {
+        Some(interface) => interface,
+        None => return Responser::new(Some("接口不存在"), &status::BAD_REQUEST).to_result(),
+    };
+
+    let query: Map<String, Value> = req.query().unwrap_or_default();
+    let text = req.body_string().await?;
+    let body = if text.trim().is_empty() {
+        Value::Null
+    } else {
+        match serde_json::from_str::<Value>(&text) {
+            Ok(body) => body,
+            Err(e) => {
+                return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result()
+            }
+        }
+    };
+    let violations = validate(interface, &query, &body);
+    if !violations.is_empty() {
+        return Responser::new(Some(violations), &status::BAD_REQUEST).to_result();
+    }
+
+    // 固定示例原样返回，否则按响应字段生成 data
+    match &interface.example {
+        Some(example) => {
+            let mut res = Response::new(StatusCode::Ok);
+            res.set_body(example.clone());
+            Ok(res)
+        }
+        None => Responser::new(Some(generate(&interface.response)), &status::OK).to_result(),
+    }
+}
@@ -0,0 +1,66 @@
+use chrono::prelude::{DateTime, Local};
+
+use crate::utils::my_date_format;
+
+lazy_static! {
+    pub(crate) static ref API_KEY: String = String::from("api_key");
+}
+
+// key 的格式为 tk_{prefix}_{secret}，prefix 明文保存用于查找，secret 只保存 hash
+pub(crate) const KEY_PREFIX: &str = "tk_";
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct ApiKey {
+    pub(crate) name: String,
+    pub(crate) prefix: String,
+    pub(crate) hash: String,
+    // 与路由权限格式相同，如 case:run、interface:*、*
+    pub(crate) scopes: Vec<String>,
+    pub(crate) user_id: String,
+    pub(crate) expire_at: Option<DateTime<Local>>,
+    pub(crate) last_used: Option<DateTime<Local>>,
+    #[serde(with = "my_date_format")]
+    pub(crate) create_at: DateTime<Local>,
+}
+
+impl ApiKey {
+    // 拆出 prefix 和 secret
+    pub(crate) fn split(key: &str) -> Option<(&str, &str)> {
+        let (prefix, secret) = key.strip_prefix(KEY_PREFIX)?.split_once('_')?;
+        if prefix.is_empty() || secret.is_empty() {
+            return None;
+        }
+        Some((prefix, secret))
+    }
+
+    pub(crate) fn expired(&self) -> bool {
+        self.expire_at.map(|e| e <= Local::now()).unwrap_or(false)
+    }
+}
+
+pub(crate) fn scope_allows(scopes: &[String], permission: &str) -> bool {
+    let resource = permission.split(':').next().unwrap_or_default();
+    scopes.iter().any(|s| {
+        s == "*" || s == permission || s.strip_suffix(":*") == Some(resource)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scope_allows, ApiKey};
+
+    #[test]
+    fn test_api_key() {
+        assert_eq!(ApiKey::split("tk_abc_secret"), Some(("abc", "secret")));
+        assert_eq!(ApiKey::split("tk_abc_sec_ret"), Some(("abc", "sec_ret")));
+        assert_eq!(ApiKey::split("tk__secret"), None);
+        assert_eq!(ApiKey::split("abc_secret"), None);
+
+        let scopes = vec![String::from("case:run"), String::from("interface:*")];
+        assert!(scope_allows(&scopes, "case:run"));
+        assert!(!scope_allows(&scopes, "case:write"));
+        assert!(scope_allows(&scopes, "interface:write"));
+        assert!(!scope_allows(&scopes, "step:read"));
+        assert!(scope_allows(&[String::from("*")], "user:admin"));
+    }
+}
@@ -0,0 +1,39 @@
+use chrono::prelude::{DateTime, Local};
+
+use crate::utils::my_date_format;
+
+lazy_static! {
+    pub(crate) static ref CASE: String = String::from("case");
+}
+
+// 测试场景，按顺序执行 setup、steps、teardown 中的步骤
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct Case {
+    pub(crate) name: String,
+    pub(crate) description: String,
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+    #[serde(default)]
+    pub(crate) setup: Vec<String>,
+    pub(crate) steps: Vec<String>,
+    #[serde(default)]
+    pub(crate) teardown: Vec<String>,
+    #[serde(default)]
+    pub(crate) project_id: String,
+    pub(crate) user_id: String,
+    #[serde(with = "my_date_format")]
+    pub(crate) create_at: DateTime<Local>,
+    #[serde(default, with = "my_date_format::option")]
+    pub(crate) update_at: Option<DateTime<Local>>,
+}
+
+impl Case {
+    // 所有引用到的步骤 id
+    pub(crate) fn step_ids(&self) -> Vec<&String> {
+        self.setup
+            .iter()
+            .chain(self.steps.iter())
+            .chain(self.teardown.iter())
+            .collect()
+    }
+}
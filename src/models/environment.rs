@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use chrono::prelude::{DateTime, Local};
+use serde_json::Value;
+
+use crate::utils::{decrypt, my_date_format};
+
+lazy_static! {
+    pub(crate) static ref ENVIRONMENT: String = String::from("environment");
+}
+
+// 返回给前端和写入执行记录的 secret 值，更新环境时传回该值表示不修改
+pub(crate) const SECRET_MASK: &str = "******";
+
+// 运行环境，secrets 中保存的是加密后的值
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct Environment {
+    pub(crate) name: String,
+    pub(crate) base_url: String,
+    #[serde(default)]
+    pub(crate) variables: HashMap<String, Value>,
+    #[serde(default)]
+    pub(crate) headers: HashMap<String, String>,
+    #[serde(default)]
+    pub(crate) secrets: HashMap<String, String>,
+    #[serde(default)]
+    pub(crate) project_id: String,
+    pub(crate) user_id: String,
+    #[serde(with = "my_date_format")]
+    pub(crate) create_at: DateTime<Local>,
+    #[serde(default, with = "my_date_format::option")]
+    pub(crate) update_at: Option<DateTime<Local>>,
+}
+
+impl Environment {
+    // 运行时使用的变量，secrets 解密后覆盖同名普通变量；有解密失败的返回 Err(这些 key)
+    pub(crate) fn runtime_variables(&self) -> Result<HashMap<String, Value>, Vec<String>> {
+        let mut vars = self.variables.clone();
+        let mut failed = Vec::new();
+        for (k, v) in self.secrets.iter() {
+            match decrypt(v) {
+                Some(plain) => {
+                    vars.insert(k.clone(), Value::String(plain));
+                }
+                None => failed.push(k.clone()),
+            }
+        }
+        if !failed.is_empty() {
+            failed.sort();
+            return Err(failed);
+        }
+        Ok(vars)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::prelude::Local;
+    use serde_json::json;
+
+    use super::Environment;
+    use crate::utils::encrypt;
+
+    #[test]
+    fn test_runtime_variables() {
+        let mut environment = Environment {
+            name: String::from("dev"),
+            base_url: String::from("http://127.0.0.1"),
+            variables: HashMap::new(),
+            headers: HashMap::new(),
+            secrets: HashMap::new(),
+            project_id: String::new(),
+            user_id: String::new(),
+            create_at: Local::now(),
+            update_at: None,
+        };
+        environment.variables.insert(String::from("token"), json!("plain"));
+        environment
+            .secrets
+            .insert(String::from("token"), encrypt("secret").unwrap());
+        let vars = environment.runtime_variables().unwrap();
+        assert_eq!(vars["token"], json!("secret"));
+
+        // 解密失败的变量不能悄悄丢掉
+        environment
+            .secrets
+            .insert(String::from("b"), String::from("broken"));
+        environment
+            .secrets
+            .insert(String::from("a"), String::from("broken"));
+        assert_eq!(
+            environment.runtime_variables().unwrap_err(),
+            vec![String::from("a"), String::from("b")]
+        );
+    }
+}
@@ -0,0 +1,18 @@
+use chrono::prelude::{DateTime, Local};
+
+use crate::utils::my_date_format;
+
+lazy_static! {
+    pub(crate) static ref IDENTITY: String = String::from("identity");
+}
+
+// 第三方登录帐号与 User 的绑定关系，provider + subject 唯一
+#[derive(Deserialize, Serialize, Debug)]
+pub(crate) struct Identity {
+    pub(crate) provider: String,
+    pub(crate) subject: String,
+    pub(crate) user_id: String,
+    pub(crate) email: Option<String>,
+    #[serde(with = "my_date_format")]
+    pub(crate) create_at: DateTime<Local>,
+}
@@ -3,7 +3,11 @@ use validator::Validate;
 use serde_json::Value;
 use crate::utils::my_date_format;
 
-#[derive(Deserialize, Serialize, Debug, Validate)]
+lazy_static! {
+    pub(crate) static ref INTERFACE: String = String::from("interface");
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Validate)]
 pub(crate) struct Field {
     pub(crate) name: String,
     pub(crate) required: bool,
@@ -12,9 +16,29 @@ pub(crate) struct Field {
     pub(crate) min: Option<usize>,
     pub(crate) length_min: Option<usize>,
     pub(crate) length_max: Option<usize>,
+    // object 类型的子字段
+    #[serde(default)]
+    pub(crate) fields: Vec<Field>,
+    // array 类型的元素定义，name 不使用
+    #[serde(default)]
+    pub(crate) items: Option<Box<Field>>,
+}
+
+impl Field {
+    // 统一成 JSON Schema 的类型名
+    pub(crate) fn schema_type(&self) -> &'static str {
+        match self.data_type.to_lowercase().as_str() {
+            "int" | "integer" | "long" => "integer",
+            "float" | "double" | "number" => "number",
+            "bool" | "boolean" => "boolean",
+            "object" | "map" => "object",
+            "array" | "list" => "array",
+            _ => "string",
+        }
+    }
 }
 
-#[derive(Deserialize, Serialize, Debug, Validate)]
+#[derive(Deserialize, Serialize, Debug, Clone, Validate)]
 pub(crate) struct Interface {
     pub(crate) url: String,
     pub(crate) description: String,
@@ -24,6 +48,17 @@ pub(crate) struct Interface {
     pub(crate) data: Vec<Field>,
     #[validate]
     pub(crate) param: Vec<Field>,
+    // 响应字段，mock 时按类型生成数据
+    #[serde(default)]
+    pub(crate) response: Vec<Field>,
+    // 固定的响应示例，优先于 response 生成的数据
+    #[serde(default)]
+    pub(crate) example: Option<Value>,
+    #[serde(default)]
+    pub(crate) project_id: String,
+    pub(crate) user_id: String,
     #[serde(with = "my_date_format")]
     pub(crate) create_at: DateTime<Local>,
+    #[serde(default, with = "my_date_format::option")]
+    pub(crate) update_at: Option<DateTime<Local>>,
 }
@@ -1,7 +1,21 @@
+mod api_key;
+mod case;
+mod environment;
+mod identity;
 mod interfaces;
+mod password_history;
+mod project;
+mod run;
+mod step;
 mod users;
 
-pub(crate) use interfaces::{Field, Interface};
-pub(crate) use users::{User, USER};
-pub(crate) use step::Step;
-pub(crate) use case::Case;
+pub(crate) use api_key::{scope_allows, ApiKey, API_KEY, KEY_PREFIX};
+pub(crate) use case::{Case, CASE};
+pub(crate) use environment::{Environment, ENVIRONMENT, SECRET_MASK};
+pub(crate) use identity::{Identity, IDENTITY};
+pub(crate) use interfaces::{Field, Interface, INTERFACE};
+pub(crate) use password_history::{PasswordHistory, PASSWORD_HISTORY};
+pub(crate) use project::{Invite, Member, Project, PROJECT};
+pub(crate) use run::{AssertionResult, Run, Stage, StepResult, RUN};
+pub(crate) use step::{Assertion, Expect, Extract, Source, Step, STEP};
+pub(crate) use users::{EmailChange, Mfa, Role, User, USER};
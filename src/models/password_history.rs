@@ -0,0 +1,16 @@
+use chrono::prelude::{DateTime, Local};
+
+use crate::utils::my_date_format;
+
+lazy_static! {
+    pub(crate) static ref PASSWORD_HISTORY: String = String::from("password_history");
+}
+
+// 用过的密码 hash，只保留最近 PasswordPolicy.history 条
+#[derive(Deserialize, Serialize, Debug)]
+pub(crate) struct PasswordHistory {
+    pub(crate) user_id: String,
+    pub(crate) hash: String,
+    #[serde(with = "my_date_format")]
+    pub(crate) create_at: DateTime<Local>,
+}
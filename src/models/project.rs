@@ -0,0 +1,139 @@
+use chrono::prelude::{DateTime, Local};
+
+use super::Role;
+use crate::utils::my_date_format;
+
+lazy_static! {
+    pub(crate) static ref PROJECT: String = String::from("project");
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct Member {
+    pub(crate) user_id: String,
+    pub(crate) role: Role,
+    #[serde(with = "my_date_format")]
+    pub(crate) join_at: DateTime<Local>,
+}
+
+// 已发出还未接受的邀请，同一邮箱只保留一条
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct Invite {
+    pub(crate) email: String,
+    pub(crate) role: Role,
+    // 邀请人
+    pub(crate) user_id: String,
+    #[serde(with = "my_date_format")]
+    pub(crate) create_at: DateTime<Local>,
+}
+
+// 项目是接口、步骤、用例和环境的归属单位，成员在项目内有各自的角色
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct Project {
+    pub(crate) name: String,
+    pub(crate) description: String,
+    #[serde(default)]
+    pub(crate) members: Vec<Member>,
+    #[serde(default)]
+    pub(crate) invites: Vec<Invite>,
+    pub(crate) user_id: String,
+    #[serde(with = "my_date_format")]
+    pub(crate) create_at: DateTime<Local>,
+    #[serde(default, with = "my_date_format::option")]
+    pub(crate) update_at: Option<DateTime<Local>>,
+}
+
+impl Project {
+    // 引入项目之前的数据迁移到创建者的默认项目中
+    pub(crate) fn default_for(user_id: &str) -> Project {
+        Project {
+            name: String::from("默认项目"),
+            description: String::new(),
+            members: vec![Member {
+                user_id: user_id.to_string(),
+                role: Role::Admin,
+                join_at: Local::now(),
+            }],
+            invites: Vec::new(),
+            user_id: user_id.to_string(),
+            create_at: Local::now(),
+            update_at: None,
+        }
+    }
+
+    pub(crate) fn role(&self, user_id: &str) -> Option<Role> {
+        self.members
+            .iter()
+            .find(|m| m.user_id == user_id)
+            .map(|m| m.role)
+    }
+
+    // 修改或移除 user_id 后是否还有管理员
+    pub(crate) fn keeps_admin(&self, user_id: &str, role: Option<Role>) -> bool {
+        role == Some(Role::Admin)
+            || self
+                .members
+                .iter()
+                .any(|m| m.user_id != user_id && m.role == Role::Admin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::Local;
+    use mongodb::bson::{from_document, to_document};
+
+    use super::{Member, Project};
+    use crate::models::Role;
+
+    #[test]
+    fn test_keeps_admin() {
+        let member = |user_id: &str, role| Member {
+            user_id: user_id.to_string(),
+            role,
+            join_at: Local::now(),
+        };
+        let mut project = Project {
+            name: String::from("demo"),
+            description: String::new(),
+            members: vec![member("a", Role::Admin), member("b", Role::Tester)],
+            invites: Vec::new(),
+            user_id: String::from("a"),
+            create_at: Local::now(),
+            update_at: None,
+        };
+        assert_eq!(project.role("b"), Some(Role::Tester));
+        assert_eq!(project.role("c"), None);
+        assert!(!project.keeps_admin("a", None));
+        assert!(!project.keeps_admin("a", Some(Role::Maintainer)));
+        assert!(project.keeps_admin("a", Some(Role::Admin)));
+        assert!(project.keeps_admin("b", None));
+
+        project.members.push(member("c", Role::Admin));
+        assert!(project.keeps_admin("a", None));
+    }
+
+    #[test]
+    fn test_update_at_format() {
+        let mut project = Project {
+            name: String::from("demo"),
+            description: String::new(),
+            members: Vec::new(),
+            invites: Vec::new(),
+            user_id: String::from("a"),
+            create_at: Local::now(),
+            update_at: None,
+        };
+        let doc = to_document(&project).unwrap();
+        assert!(from_document::<Project>(doc).unwrap().update_at.is_none());
+
+        // update_at 和 create_at 存成同一格式
+        project.update_at = Some(project.create_at);
+        let doc = to_document(&project).unwrap();
+        assert_eq!(doc.get_str("update_at").unwrap(), doc.get_str("create_at").unwrap());
+        let project = from_document::<Project>(doc).unwrap();
+        assert_eq!(
+            project.update_at.map(|d| d.timestamp()),
+            Some(project.create_at.timestamp())
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use chrono::prelude::{DateTime, Local};
+use serde_json::Value;
+
+use crate::utils::my_date_format;
+
+lazy_static! {
+    pub(crate) static ref RUN: String = String::from("run");
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Stage {
+    Setup,
+    Step,
+    Teardown,
+}
+
+// 单个断言的结果
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct AssertionResult {
+    pub(crate) kind: String,
+    pub(crate) target: Option<String>,
+    pub(crate) expected: Value,
+    pub(crate) actual: Value,
+    pub(crate) passed: bool,
+    pub(crate) message: Option<String>,
+}
+
+// 单个步骤的执行记录
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct StepResult {
+    pub(crate) step_id: String,
+    pub(crate) name: String,
+    pub(crate) stage: Stage,
+    pub(crate) method: String,
+    pub(crate) url: String,
+    pub(crate) status: Option<u16>,
+    pub(crate) latency: u64,
+    pub(crate) headers: HashMap<String, String>,
+    pub(crate) body: Value,
+    pub(crate) error: Option<String>,
+    #[serde(default)]
+    pub(crate) assertions: Vec<AssertionResult>,
+    #[serde(default)]
+    pub(crate) variables: HashMap<String, Value>,
+    pub(crate) passed: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub(crate) struct Run {
+    pub(crate) case_id: String,
+    pub(crate) env_id: Option<String>,
+    pub(crate) base_url: String,
+    pub(crate) passed: bool,
+    // 没能开始执行的原因，例如密文变量无法解密
+    #[serde(default)]
+    pub(crate) error: Option<String>,
+    #[serde(default)]
+    pub(crate) results: Vec<StepResult>,
+    #[serde(default)]
+    pub(crate) project_id: String,
+    pub(crate) user_id: String,
+    #[serde(with = "my_date_format")]
+    pub(crate) create_at: DateTime<Local>,
+}
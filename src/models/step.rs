@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use chrono::prelude::{DateTime, Local};
+use serde_json::{Map, Value};
+
+use crate::utils::my_date_format;
+
+lazy_static! {
+    pub(crate) static ref STEP: String = String::from("step");
+}
+
+// 步骤的期望结果，未设置的项不做检查
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub(crate) struct Expect {
+    pub(crate) status: Option<u16>,
+    pub(crate) body: Option<Value>,
+}
+
+// 对响应的断言，path 为 JSONPath，例如 $.data.list[0].id
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum Assertion {
+    Status { expected: u16 },
+    JsonEquals { path: String, expected: Value },
+    JsonContains { path: String, expected: Value },
+    JsonRegex { path: String, pattern: String },
+    Header { name: String },
+    ResponseTime { max: u64 },
+    JsonSchema { schema: Value },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Source {
+    Body,
+    Header,
+}
+
+// 从响应中提取变量，from 为 body 时 path 是 JSONPath，为 header 时是头名称
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct Extract {
+    pub(crate) name: String,
+    pub(crate) from: Source,
+    pub(crate) path: String,
+}
+
+// 一次对已保存 Interface 的调用，url、param、headers、data 中可以使用 {{var}}
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct Step {
+    pub(crate) name: String,
+    pub(crate) interface_id: String,
+    #[serde(default)]
+    pub(crate) param: Map<String, Value>,
+    #[serde(default)]
+    pub(crate) data: Value,
+    #[serde(default)]
+    pub(crate) headers: HashMap<String, String>,
+    #[serde(default)]
+    pub(crate) expect: Expect,
+    #[serde(default)]
+    pub(crate) assertions: Vec<Assertion>,
+    #[serde(default)]
+    pub(crate) extract: Vec<Extract>,
+    #[serde(default)]
+    pub(crate) project_id: String,
+    pub(crate) user_id: String,
+    #[serde(with = "my_date_format")]
+    pub(crate) create_at: DateTime<Local>,
+    #[serde(default, with = "my_date_format::option")]
+    pub(crate) update_at: Option<DateTime<Local>>,
+}
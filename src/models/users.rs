@@ -1,11 +1,50 @@
+use std::convert::TryFrom;
+
 use crate::auth::Register;
-use crate::utils::hash_password;
-use chrono::prelude::{DateTime, Local};
+use crate::utils::{decrypt, hash_password, password_verify, rand_str, totp};
+use crate::CONFIG;
+use chrono::{
+    prelude::{DateTime, Local},
+    Duration,
+};
 
 lazy_static! {
     pub(crate) static ref USER: String = String::from("user");
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Role {
+    Admin,
+    Maintainer,
+    Tester,
+    Viewer,
+}
+
+impl Role {
+    // 权限格式为 资源:操作，admin 拥有全部权限
+    pub(crate) fn allows(&self, permission: &str) -> bool {
+        let (resource, action) = match permission.split_once(':') {
+            Some(p) => p,
+            None => return false,
+        };
+        match self {
+            Role::Admin => true,
+            Role::Maintainer => match resource {
+                "user" | "member" => action == "read",
+                "project" => action != "delete",
+                _ => true,
+            },
+            Role::Tester => match resource {
+                "step" | "case" => true,
+                "interface" | "environment" | "user" => action == "read",
+                _ => false,
+            },
+            Role::Viewer => action == "read",
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub(crate) struct User {
     pub(crate) username: String,
@@ -13,22 +52,197 @@ pub(crate) struct User {
     pub(crate) password: String,
     pub(crate) phone: String,
     pub(crate) active: bool,
+    #[serde(default = "default_roles")]
+    pub(crate) roles: Vec<Role>,
+    #[serde(default)]
+    pub(crate) mfa: Option<Mfa>,
+    #[serde(default)]
+    pub(crate) email_change: Option<EmailChange>,
     pub(crate) create_at: DateTime<Local>,
     pub(crate) update_at: Option<DateTime<Local>>,
 }
 
-impl From<Register> for User {
-    fn from(r: Register) -> User {
+// 修改邮箱，新邮箱确认后才切换，旧邮箱在撤销期内可以撤销
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct EmailChange {
+    pub(crate) old_email: String,
+    pub(crate) new_email: String,
+    pub(crate) request_at: DateTime<Local>,
+    // 新邮箱确认的时间，为空表示还未切换
+    pub(crate) confirm_at: Option<DateTime<Local>>,
+}
+
+impl EmailChange {
+    // 已切换且还在撤销期内，不允许再次修改，避免旧邮箱的撤销链接被覆盖
+    pub(crate) fn locked(&self, now: DateTime<Local>, grace: usize) -> bool {
+        self.confirm_at.is_some() && now < self.request_at + Duration::seconds(grace as i64)
+    }
+}
+
+fn default_roles() -> Vec<Role> {
+    vec![Role::Viewer]
+}
+
+// 新建帐号的角色，配置中的管理员邮箱为管理员
+fn initial_roles(email: &str) -> Vec<Role> {
+    if CONFIG.is_admin_email(email) {
+        vec![Role::Admin]
+    } else {
+        default_roles()
+    }
+}
+
+const RECOVERY_COUNT: usize = 10;
+
+// 两步验证，secret 使用 encrypt 加密保存，recovery 为恢复码的 hash
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct Mfa {
+    pub(crate) secret: String,
+    // 用第一个验证码确认后才开启
+    pub(crate) enabled: bool,
+    #[serde(default)]
+    pub(crate) recovery: Vec<String>,
+    // 最后一次通过验证的周期，同一个验证码不能重复使用
+    #[serde(default)]
+    pub(crate) last_step: i64,
+}
+
+impl Mfa {
+    // 返回恢复码明文和对应的 hash，明文只展示一次
+    pub(crate) fn new_recovery() -> Result<(Vec<String>, Vec<String>), argon2::Error> {
+        let codes: Vec<String> = (0..RECOVERY_COUNT)
+            .map(|_| rand_str(10).to_lowercase())
+            .collect();
+        let hashes = codes
+            .iter()
+            .map(|c| hash_password(c))
+            .collect::<Result<_, _>>()?;
+        Ok((codes, hashes))
+    }
+
+    // 校验 TOTP 验证码或恢复码，通过时返回需要保存的新状态
+    pub(crate) fn verify(&self, code: &str, now: u64) -> Option<Mfa> {
+        let code = code.trim().to_lowercase();
+        if code.len() == 6 {
+            let secret = decrypt(&self.secret)?;
+            let step = totp::verify(&secret, &code, now, self.last_step as u64)?;
+            return Some(Mfa {
+                last_step: step as i64,
+                ..self.clone()
+            });
+        }
+        let used = self.recovery.iter().position(|h| password_verify(h, &code))?;
+        let mut mfa = self.clone();
+        mfa.recovery.remove(used);
+        Some(mfa)
+    }
+}
+
+impl TryFrom<Register> for User {
+    type Error = argon2::Error;
+
+    fn try_from(r: Register) -> Result<User, argon2::Error> {
         let now = Local::now().into();
-        let password = hash_password(&r.password);
-        User {
+        let password = hash_password(&r.password)?;
+        Ok(User {
             username: r.username,
+            roles: initial_roles(&r.email),
             email: r.email,
             phone: r.phone,
             password,
             active: false,
+            mfa: None,
+            email_change: None,
             create_at: now,
             update_at: None,
+        })
+    }
+}
+
+impl User {
+    // 第三方登录自动创建的帐号，邮箱已由身份提供方验证，密码随机，需要时走忘记密码设置
+    pub(crate) fn provisioned(username: String, email: String) -> Result<User, argon2::Error> {
+        Ok(User {
+            username,
+            roles: initial_roles(&email),
+            email,
+            password: hash_password(&rand_str(32))?,
+            phone: String::new(),
+            active: true,
+            mfa: None,
+            email_change: None,
+            create_at: Local::now(),
+            update_at: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{prelude::Local, Duration};
+
+    use super::{initial_roles, EmailChange, Mfa, Role};
+    use crate::utils::encrypt;
+
+    #[test]
+    fn test_role_allows() {
+        let cases = vec![
+            (Role::Admin, "user:admin", true),
+            (Role::Maintainer, "interface:write", true),
+            (Role::Maintainer, "user:admin", false),
+            (Role::Maintainer, "member:write", false),
+            (Role::Maintainer, "project:delete", false),
+            (Role::Tester, "case:run", true),
+            (Role::Tester, "interface:write", false),
+            (Role::Tester, "environment:read", true),
+            (Role::Viewer, "case:read", true),
+            (Role::Viewer, "case:run", false),
+            (Role::Viewer, "case", false),
+        ];
+        for (role, permission, allowed) in cases {
+            assert_eq!(role.allows(permission), allowed, "{:?} {}", role, permission);
         }
     }
+
+    #[test]
+    fn test_initial_roles() {
+        // TEST.toml 中 admin_emails 为 lomect@example.com
+        assert_eq!(initial_roles("lomect@example.com"), vec![Role::Admin]);
+        assert_eq!(initial_roles("Lomect@Example.com"), vec![Role::Admin]);
+        assert_eq!(initial_roles("other@example.com"), vec![Role::Viewer]);
+    }
+
+    #[test]
+    fn test_mfa_verify() {
+        let (codes, recovery) = Mfa::new_recovery().unwrap();
+        let mfa = Mfa {
+            secret: encrypt("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap(),
+            enabled: true,
+            recovery,
+            last_step: 0,
+        };
+        let mfa = mfa.verify("287082", 59).unwrap();
+        assert_eq!(mfa.last_step, 1);
+        assert!(mfa.verify("287082", 59).is_none());
+
+        let mfa = mfa.verify(&codes[3].to_uppercase(), 59).unwrap();
+        assert_eq!(mfa.recovery.len(), codes.len() - 1);
+        assert!(mfa.verify(&codes[3], 59).is_none());
+        assert!(mfa.verify("wrong-code", 59).is_none());
+    }
+
+    #[test]
+    fn test_email_change_locked() {
+        let now = Local::now();
+        let mut change = EmailChange {
+            old_email: String::from("old@example.com"),
+            new_email: String::from("new@example.com"),
+            request_at: now - Duration::hours(1),
+            confirm_at: None,
+        };
+        assert!(!change.locked(now, 3600 * 24));
+        change.confirm_at = Some(now);
+        assert!(change.locked(now, 3600 * 24));
+        assert!(!change.locked(now + Duration::days(1), 3600 * 24));
+    }
 }
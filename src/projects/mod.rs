@@ -0,0 +1,37 @@
+mod routers;
+mod schema;
+
+use tide::Server;
+
+use crate::middleware::{guard, LoginMiddleware, ProjectMiddleware};
+use crate::State;
+use routers::{
+    add_project, delete_project, get_project, invite_member, join_project, list_member,
+    list_project, remove_member, set_member, update_project,
+};
+
+pub(crate) fn project_router(app: &mut Server<State>) {
+    let mut project = app.at("/project");
+    project.with(LoginMiddleware);
+    project.at("/").get(list_project);
+    project.at("/add").post(guard("project:create", add_project));
+    project.at("/join").post(join_project);
+
+    // 项目内的路由按成员在项目中的角色判断权限
+    let mut scoped = app.at("/project/:pid");
+    scoped.with(LoginMiddleware).with(ProjectMiddleware);
+    scoped
+        .get(guard("project:read", get_project))
+        .put(guard("project:write", update_project))
+        .delete(guard("project:delete", delete_project));
+    scoped
+        .at("/members")
+        .get(guard("member:read", list_member));
+    scoped
+        .at("/members/:uid")
+        .put(guard("member:write", set_member))
+        .delete(guard("member:write", remove_member));
+    scoped
+        .at("/invite")
+        .post(guard("member:write", invite_member));
+}
@@ -0,0 +1,340 @@
+use async_std::task;
+use chrono::prelude::Local;
+use mongodb::bson::{doc, from_document, oid::ObjectId, to_bson, Document};
+use tide::{log, Request};
+use validator::Validate;
+
+use super::schema::{
+    AddProject, GetProject, InviteMember, ResMember, ResMembers, ResProject, SetMember,
+};
+use crate::db::{find_user, find_user_by, MongoDb, Options, INVITE_EXPIRE_TIME};
+use crate::middleware::{CurrentUser, Token};
+use crate::models::{
+    Invite, Member, Project, CASE, ENVIRONMENT, INTERFACE, PROJECT, RUN, STEP, USER,
+};
+use crate::utils::*;
+use crate::{State, CONFIG};
+
+fn to_res_project(data: Document) -> Option<ResProject> {
+    let id = data.get_object_id("_id").ok()?.to_hex();
+    let project = from_document::<Project>(data).ok()?;
+    Some(ResProject { id, project })
+}
+
+// 路由经过 ProjectMiddleware，项目一定存在
+async fn find_project(mongo: &MongoDb, pid: &str) -> tide::Result<Project> {
+    let mut opt = Options::default();
+    opt.find_one_opt(&PROJECT, Some(doc! { "_id": ObjectId::with_string(pid)? }));
+    match mongo.find(opt).await?.pop() {
+        Some(data) => Ok(from_document(data)?),
+        None => Err(tide::Error::from_str(tide::StatusCode::NotFound, "项目不存在")),
+    }
+}
+
+pub(crate) async fn add_project(mut req: Request<State>) -> tide::Result {
+    let data: AddProject = match req.body_json().await {
+        Ok(data) => data,
+        Err(error) => {
+            return Responser::new(Some(format!("{}", error)), &status::BAD_REQUEST).to_result()
+        }
+    };
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+
+    let project = data.into_project(user_id);
+    let mut opt = Options::default();
+    opt.set_collect(&PROJECT);
+    let id = req.state().mongo.insert_one(opt, &project).await?;
+    Responser::new(Some(ResProject { id, project }), &status::OK).to_result()
+}
+
+// 只列出自己参与的项目
+pub(crate) async fn list_project(req: Request<State>) -> tide::Result {
+    let filter: GetProject = match req.query() {
+        Ok(res) => res,
+        Err(e) => return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result(),
+    };
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+
+    let mut condition = doc! { "members.user_id": user_id };
+    if let Some(name) = filter.name {
+        condition.insert("name", name);
+    }
+
+    let skip = (filter.page.page_num.max(1) - 1) * filter.page.page_size;
+    let opt = Options::new(
+        &PROJECT,
+        Some(condition),
+        Some(filter.page.page_size as i64),
+        Some(skip as i64),
+        Some(doc! { "_id": -1 }),
+        Some(doc! { "invites": 0 }),
+    );
+    let data: Vec<ResProject> = req
+        .state()
+        .mongo
+        .find(opt)
+        .await?
+        .into_iter()
+        .filter_map(to_res_project)
+        .collect();
+    Responser::new(Some(data), &status::OK).to_result()
+}
+
+pub(crate) async fn get_project(req: Request<State>) -> tide::Result {
+    let id = req.param::<String>("pid")?;
+    let project = find_project(&req.state().mongo, &id).await?;
+    Responser::new(Some(ResProject { id, project }), &status::OK).to_result()
+}
+
+pub(crate) async fn update_project(mut req: Request<State>) -> tide::Result {
+    let data: AddProject = match req.body_json().await {
+        Ok(data) => data,
+        Err(error) => {
+            return Responser::new(Some(format!("{}", error)), &status::BAD_REQUEST).to_result()
+        }
+    };
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let id = req.param::<String>("pid")?;
+
+    let update = doc! { "$set": {
+        "name": &data.name,
+        "description": &data.description,
+        "update_at": my_date_format::now(),
+    }};
+    let mut opt = Options::default();
+    opt.update_opt(&PROJECT, Some(doc! { "_id": ObjectId::with_string(&id)? }), None);
+    req.state().mongo.update(update, opt).await?;
+    Responser::new(Some(id), &status::OK).to_result()
+}
+
+// 删除项目时一并删除项目中的接口、步骤、用例、环境和执行记录
+pub(crate) async fn delete_project(req: Request<State>) -> tide::Result {
+    let id = req.param::<String>("pid")?;
+    let mongo = &req.state().mongo;
+    for collect in [&*INTERFACE, &*STEP, &*CASE, &*ENVIRONMENT, &*RUN].iter() {
+        let mut opt = Options::default();
+        opt.del_opt(collect, Some(doc! { "project_id": &id }), Some(0));
+        mongo.delete(opt).await?;
+    }
+
+    let mut opt = Options::default();
+    opt.del_opt(&PROJECT, Some(doc! { "_id": ObjectId::with_string(&id)? }), None);
+    mongo.delete(opt).await?;
+    Responser::new(Some(id), &status::OK).to_result()
+}
+
+pub(crate) async fn list_member(req: Request<State>) -> tide::Result {
+    let id = req.param::<String>("pid")?;
+    let mongo = &req.state().mongo;
+    let project = find_project(mongo, &id).await?;
+
+    let oids = project
+        .members
+        .iter()
+        .filter_map(|m| ObjectId::with_string(&m.user_id).ok())
+        .collect::<Vec<ObjectId>>();
+    let mut opt = Options::default();
+    opt.set_collect(&USER);
+    opt.filter = Some(doc! { "_id": { "$in": oids } });
+    opt.fileds = Some(doc! { "username": 1, "email": 1 });
+    let users = mongo.find(opt).await?;
+
+    let members = project
+        .members
+        .into_iter()
+        .map(|m| {
+            let user = users.iter().find(|u| {
+                u.get_object_id("_id")
+                    .map(|oid| oid.to_hex() == m.user_id)
+                    .unwrap_or(false)
+            });
+            let field = |name: &str| {
+                user.and_then(|u| u.get_str(name).ok())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            ResMember {
+                username: field("username"),
+                email: field("email"),
+                user_id: m.user_id,
+                role: m.role,
+                join_at: m.join_at,
+            }
+        })
+        .collect();
+    let res = ResMembers {
+        members,
+        invites: project.invites,
+    };
+    Responser::new(Some(res), &status::OK).to_result()
+}
+
+// 邀请链接发送到邮箱，被邀请人登录后凭链接中的 token 加入
+pub(crate) async fn invite_member(mut req: Request<State>) -> tide::Result {
+    let mut data: InviteMember = match req.body_json().await {
+        Ok(data) => data,
+        Err(error) => {
+            return Responser::new(Some(format!("{}", error)), &status::BAD_REQUEST).to_result()
+        }
+    };
+    // 帐号邮箱按小写保存，邀请也按小写匹配
+    data.email = data.email.to_lowercase();
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let id = req.param::<String>("pid")?;
+    let mongo = &req.state().mongo;
+    let project = find_project(mongo, &id).await?;
+
+    if let Some((uid, _)) = find_user_by(mongo, doc! { "email": &data.email }).await? {
+        if project.role(&uid).is_some() {
+            return Responser::new(Some("已经是项目成员"), &status::BAD_REQUEST).to_result();
+        }
+    }
+
+    // 重复邀请时覆盖之前的邀请
+    let filter = doc! { "_id": ObjectId::with_string(&id)? };
+    let invite = Invite {
+        email: data.email.clone(),
+        role: data.role,
+        user_id,
+        create_at: Local::now(),
+    };
+    let mut opt = Options::default();
+    opt.update_opt(&PROJECT, Some(filter.clone()), None);
+    let update = doc! { "$pull": { "invites": { "email": &data.email } } };
+    mongo.update(update, opt).await?;
+    let mut opt = Options::default();
+    opt.update_opt(&PROJECT, Some(filter), None);
+    let update = doc! { "$push": { "invites": to_bson(&invite)? } };
+    mongo.update(update, opt).await?;
+
+    let redis_cli = &req.state().redis;
+    let invite_id = format!("{}:{}", id, data.email);
+    let token = redis_cli
+        .set_once_token("invite", &invite_id, *INVITE_EXPIRE_TIME)
+        .await?;
+    let subject = format!("Project Invite: {}", project.name);
+    let link = format!("{}/api/v1/project/join/{}", CONFIG.server.domain, token);
+    // 后台发送，邀请已经写入，发送失败时可以重新邀请
+    task::spawn(async move {
+        if let Err(e) = send_email(&data.email, &subject, &link).await {
+            log::error!("Send invite email error: {}", e);
+        }
+    });
+    Responser::new(Some("success"), &status::OK).to_result()
+}
+
+// 只有受邀邮箱对应的帐号可以加入
+pub(crate) async fn join_project(mut req: Request<State>) -> tide::Result {
+    let token: Token = req.body_json().await?;
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let redis_cli = &req.state().redis;
+    let mongo = &req.state().mongo;
+
+    // 邮箱不一致时不消耗链接，受邀人仍然可以使用
+    let invite_id = match redis_cli.peek_once_token("invite", &token.token).await? {
+        Some(invite_id) => invite_id,
+        None => return Responser::new(Some("链接已失效"), &status::BAD_REQUEST).to_result(),
+    };
+    let (id, email) = match invite_id.split_once(':') {
+        Some((id, email)) => (id.to_string(), email.to_string()),
+        None => return Responser::new(Some("链接已失效"), &status::BAD_REQUEST).to_result(),
+    };
+    let user = find_user(mongo, &user_id).await?;
+    if user.map(|u| u.email) != Some(email.clone()) {
+        return Responser::new(Some("邀请的邮箱与当前帐号不一致"), &status::FORBIDDEN)
+            .to_result();
+    }
+    if redis_cli.take_once_token("invite", &token.token).await?.is_none() {
+        return Responser::new(Some("链接已失效"), &status::BAD_REQUEST).to_result();
+    }
+
+    let mut opt = Options::default();
+    opt.find_one_opt(&PROJECT, Some(doc! { "_id": ObjectId::with_string(&id)? }));
+    let project: Project = match mongo.find(opt).await?.pop() {
+        Some(data) => from_document(data)?,
+        None => return Responser::new(Some("项目不存在"), &status::BAD_REQUEST).to_result(),
+    };
+    let invite = match project.invites.iter().find(|i| i.email == email) {
+        Some(invite) => invite,
+        None => return Responser::new(Some("邀请已取消"), &status::BAD_REQUEST).to_result(),
+    };
+
+    let mut update = doc! { "$pull": { "invites": { "email": &email } } };
+    if project.role(&user_id).is_none() {
+        let member = Member {
+            user_id,
+            role: invite.role,
+            join_at: Local::now(),
+        };
+        update.insert("$push", doc! { "members": to_bson(&member)? });
+    }
+    let mut opt = Options::default();
+    opt.update_opt(&PROJECT, Some(doc! { "_id": ObjectId::with_string(&id)? }), None);
+    mongo.update(update, opt).await?;
+    Responser::new(Some(id), &status::OK).to_result()
+}
+
+pub(crate) async fn set_member(mut req: Request<State>) -> tide::Result {
+    let data: SetMember = match req.body_json().await {
+        Ok(data) => data,
+        Err(error) => {
+            return Responser::new(Some(format!("{}", error)), &status::BAD_REQUEST).to_result()
+        }
+    };
+    let id = req.param::<String>("pid")?;
+    let uid = req.param::<String>("uid")?;
+    let mongo = &req.state().mongo;
+    let project = find_project(mongo, &id).await?;
+    if project.role(&uid).is_none() {
+        return Responser::new(Some("成员不存在"), &status::BAD_REQUEST).to_result();
+    }
+    if !project.keeps_admin(&uid, Some(data.role)) {
+        return Responser::new(Some("项目至少需要一个管理员"), &status::BAD_REQUEST).to_result();
+    }
+
+    let filter = doc! { "_id": ObjectId::with_string(&id)?, "members.user_id": &uid };
+    let update = doc! { "$set": { "members.$.role": to_bson(&data.role)? } };
+    let mut opt = Options::default();
+    opt.update_opt(&PROJECT, Some(filter), None);
+    mongo.update(update, opt).await?;
+    Responser::new(Some(uid), &status::OK).to_result()
+}
+
+pub(crate) async fn remove_member(req: Request<State>) -> tide::Result {
+    let id = req.param::<String>("pid")?;
+    let uid = req.param::<String>("uid")?;
+    let mongo = &req.state().mongo;
+    let project = find_project(mongo, &id).await?;
+    if project.role(&uid).is_none() {
+        return Responser::new(Some("成员不存在"), &status::BAD_REQUEST).to_result();
+    }
+    if !project.keeps_admin(&uid, None) {
+        return Responser::new(Some("项目至少需要一个管理员"), &status::BAD_REQUEST).to_result();
+    }
+
+    let update = doc! { "$pull": { "members": { "user_id": &uid } } };
+    let mut opt = Options::default();
+    opt.update_opt(&PROJECT, Some(doc! { "_id": ObjectId::with_string(&id)? }), None);
+    mongo.update(update, opt).await?;
+    Responser::new(Some(uid), &status::OK).to_result()
+}
@@ -0,0 +1,73 @@
+use chrono::prelude::{DateTime, Local};
+use validator::Validate;
+
+use crate::models::{Invite, Member, Project, Role};
+use crate::utils::{my_date_format, Page};
+
+#[derive(Deserialize, Validate)]
+pub(crate) struct AddProject {
+    #[validate(length(min = 1, message = "name can not be empty"))]
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) description: String,
+}
+
+impl AddProject {
+    // 创建者为项目管理员
+    pub(crate) fn into_project(self, user_id: String) -> Project {
+        Project {
+            name: self.name,
+            description: self.description,
+            members: vec![Member {
+                user_id: user_id.clone(),
+                role: Role::Admin,
+                join_at: Local::now(),
+            }],
+            invites: Vec::new(),
+            user_id,
+            create_at: Local::now(),
+            update_at: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub(crate) struct GetProject {
+    pub(crate) name: Option<String>,
+    pub(crate) page: Page,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ResProject {
+    pub(crate) id: String,
+    #[serde(flatten)]
+    pub(crate) project: Project,
+}
+
+#[derive(Deserialize, Validate)]
+pub(crate) struct InviteMember {
+    #[validate(email(message = "email type error"))]
+    pub(crate) email: String,
+    pub(crate) role: Role,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SetMember {
+    pub(crate) role: Role,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ResMember {
+    pub(crate) user_id: String,
+    pub(crate) username: String,
+    pub(crate) email: String,
+    pub(crate) role: Role,
+    #[serde(with = "my_date_format")]
+    pub(crate) join_at: DateTime<Local>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ResMembers {
+    pub(crate) members: Vec<ResMember>,
+    pub(crate) invites: Vec<Invite>,
+}
@@ -0,0 +1,223 @@
+use jsonschema::JSONSchema;
+use regex::Regex;
+use serde_json::{json, Value};
+
+use super::json_contains;
+use super::json_path::select;
+use crate::models::{Assertion, AssertionResult, Expect, StepResult};
+
+// 把 Step.expect 转成等价的断言
+pub(crate) fn from_expect(expect: &Expect) -> Vec<Assertion> {
+    let mut res = Vec::new();
+    if let Some(status) = expect.status {
+        res.push(Assertion::Status { expected: status });
+    }
+    if let Some(body) = &expect.body {
+        res.push(Assertion::JsonContains {
+            path: String::from("$"),
+            expected: body.clone(),
+        });
+    }
+    res
+}
+
+// 保存步骤前检查断言本身是否合法
+pub(crate) fn verify(assertions: &[Assertion]) -> Vec<String> {
+    let mut errors = Vec::new();
+    for assertion in assertions {
+        match assertion {
+            Assertion::JsonRegex { pattern, .. } => {
+                if let Err(e) = Regex::new(pattern) {
+                    errors.push(format!("pattern {} error: {}", pattern, e));
+                }
+            }
+            Assertion::JsonSchema { schema } => {
+                if let Err(e) = JSONSchema::compile(schema) {
+                    errors.push(format!("schema error: {}", e));
+                }
+            }
+            _ => {}
+        }
+    }
+    errors
+}
+
+pub(crate) fn evaluate(assertion: &Assertion, result: &StepResult) -> AssertionResult {
+    let missing = |kind: &str, path: &str, expected: Value| AssertionResult {
+        kind: kind.to_string(),
+        target: Some(path.to_string()),
+        expected,
+        actual: Value::Null,
+        passed: false,
+        message: Some(format!("{} not found", path)),
+    };
+
+    match assertion {
+        Assertion::Status { expected } => {
+            let actual = result.status;
+            AssertionResult {
+                kind: String::from("status"),
+                target: None,
+                expected: json!(expected),
+                actual: json!(actual),
+                passed: actual == Some(*expected),
+                message: None,
+            }
+        }
+        Assertion::JsonEquals { path, expected } => match select(&result.body, path) {
+            Some(actual) => AssertionResult {
+                kind: String::from("json_equals"),
+                target: Some(path.clone()),
+                expected: expected.clone(),
+                actual: actual.clone(),
+                passed: actual == expected,
+                message: None,
+            },
+            None => missing("json_equals", path, expected.clone()),
+        },
+        Assertion::JsonContains { path, expected } => match select(&result.body, path) {
+            Some(actual) => {
+                // 字符串之间按子串判断
+                let passed = match (actual, expected) {
+                    (Value::String(a), Value::String(e)) => a.contains(e.as_str()),
+                    (a, e) => json_contains(a, e),
+                };
+                AssertionResult {
+                    kind: String::from("json_contains"),
+                    target: Some(path.clone()),
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                    passed,
+                    message: None,
+                }
+            }
+            None => missing("json_contains", path, expected.clone()),
+        },
+        Assertion::JsonRegex { path, pattern } => match select(&result.body, path) {
+            Some(actual) => {
+                let text = match actual {
+                    Value::String(s) => s.clone(),
+                    v => v.to_string(),
+                };
+                let (passed, message) = match Regex::new(pattern) {
+                    Ok(re) => (re.is_match(&text), None),
+                    Err(e) => (false, Some(e.to_string())),
+                };
+                AssertionResult {
+                    kind: String::from("json_regex"),
+                    target: Some(path.clone()),
+                    expected: json!(pattern),
+                    actual: actual.clone(),
+                    passed,
+                    message,
+                }
+            }
+            None => missing("json_regex", path, json!(pattern)),
+        },
+        Assertion::Header { name } => {
+            let actual = result
+                .headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| json!(v))
+                .unwrap_or(Value::Null);
+            AssertionResult {
+                kind: String::from("header"),
+                target: Some(name.clone()),
+                expected: json!("present"),
+                passed: !actual.is_null(),
+                actual,
+                message: None,
+            }
+        }
+        Assertion::ResponseTime { max } => AssertionResult {
+            kind: String::from("response_time"),
+            target: None,
+            expected: json!(max),
+            actual: json!(result.latency),
+            passed: result.latency < *max,
+            message: None,
+        },
+        Assertion::JsonSchema { schema } => {
+            let (passed, message) = match JSONSchema::compile(schema) {
+                Ok(compiled) => match compiled.validate(&result.body) {
+                    Ok(_) => (true, None),
+                    Err(errors) => {
+                        let errors = errors.map(|e| e.to_string()).collect::<Vec<String>>();
+                        (false, Some(errors.join("; ")))
+                    }
+                },
+                Err(e) => (false, Some(e.to_string())),
+            };
+            AssertionResult {
+                kind: String::from("json_schema"),
+                target: None,
+                expected: schema.clone(),
+                actual: result.body.clone(),
+                passed,
+                message,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::evaluate;
+    use crate::models::{Assertion, Stage, StepResult};
+
+    #[test]
+    fn test_evaluate() {
+        let mut headers = HashMap::new();
+        headers.insert(String::from("content-type"), String::from("application/json"));
+        let result = StepResult {
+            step_id: String::new(),
+            name: String::new(),
+            stage: Stage::Step,
+            method: String::from("GET"),
+            url: String::new(),
+            status: Some(200),
+            latency: 30,
+            headers,
+            body: json!({ "code": 1000, "data": { "email": "a@qq.com", "tags": ["x"] } }),
+            error: None,
+            assertions: Vec::new(),
+            variables: HashMap::new(),
+            passed: false,
+        };
+
+        let cases = vec![
+            (Assertion::Status { expected: 200 }, true),
+            (Assertion::Status { expected: 201 }, false),
+            (Assertion::JsonEquals { path: "$.code".into(), expected: json!(1000) }, true),
+            (Assertion::JsonEquals { path: "$.nope".into(), expected: json!(1) }, false),
+            (Assertion::JsonContains { path: "$.data.email".into(), expected: json!("@qq") }, true),
+            (Assertion::JsonContains { path: "$.data.tags".into(), expected: json!(["x"]) }, true),
+            (Assertion::JsonRegex { path: "$.data.email".into(), pattern: "^\\w+@".into() }, true),
+            (Assertion::Header { name: "Content-Type".into() }, true),
+            (Assertion::Header { name: "X-Token".into() }, false),
+            (Assertion::ResponseTime { max: 100 }, true),
+            (Assertion::ResponseTime { max: 10 }, false),
+            (
+                Assertion::JsonSchema {
+                    schema: json!({ "type": "object", "required": ["code", "data"] }),
+                },
+                true,
+            ),
+            (
+                Assertion::JsonSchema {
+                    schema: json!({ "properties": { "code": { "type": "string" } } }),
+                },
+                false,
+            ),
+        ];
+        for (assertion, passed) in cases {
+            let res = evaluate(&assertion, &result);
+            assert_eq!(res.passed, passed, "{:?}", assertion);
+        }
+    }
+}
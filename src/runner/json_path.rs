@@ -0,0 +1,53 @@
+use serde_json::Value;
+
+// 支持 JSONPath 的子集：$、.key、['key']、[index]，只返回单个值
+pub(crate) fn select<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.trim();
+    let mut rest = path.strip_prefix('$').unwrap_or(path);
+    let mut current = value;
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('.') {
+            let end = r.find(&['.', '['][..]).unwrap_or(r.len());
+            if end == 0 {
+                return None;
+            }
+            current = current.get(&r[..end])?;
+            rest = &r[end..];
+        } else if let Some(r) = rest.strip_prefix('[') {
+            let end = r.find(']')?;
+            let key = &r[..end];
+            let quoted = key
+                .strip_prefix('\'')
+                .and_then(|k| k.strip_suffix('\''))
+                .or_else(|| key.strip_prefix('"').and_then(|k| k.strip_suffix('"')));
+            current = match quoted {
+                Some(k) => current.get(k)?,
+                None => current.get(key.trim().parse::<usize>().ok()?)?,
+            };
+            rest = &r[end + 1..];
+        } else {
+            // 省略开头的 $. 时按字段名处理
+            let end = rest.find(&['.', '['][..]).unwrap_or(rest.len());
+            current = current.get(&rest[..end])?;
+            rest = &rest[end..];
+        }
+    }
+    Some(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::select;
+    use serde_json::json;
+
+    #[test]
+    fn test_select() {
+        let value = json!({ "data": { "list": [{ "id": 1 }, { "id": 2 }], "a.b": true } });
+        assert_eq!(select(&value, "$"), Some(&value));
+        assert_eq!(select(&value, "$.data.list[1].id"), Some(&json!(2)));
+        assert_eq!(select(&value, "data.list[0]['id']"), Some(&json!(1)));
+        assert_eq!(select(&value, "$.data['a.b']"), Some(&json!(true)));
+        assert_eq!(select(&value, "$.data.list[5]"), None);
+        assert_eq!(select(&value, "$.data..list"), None);
+    }
+}
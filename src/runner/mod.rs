@@ -0,0 +1,520 @@
+mod assertion;
+mod json_path;
+mod template;
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use serde_json::{Map, Value};
+use surf::{http::Method, Client, Url};
+
+use crate::models::{Field, Interface, Source, Stage, Step, StepResult, SECRET_MASK};
+pub(crate) use assertion::verify as verify_assertions;
+
+// 已解析出 Interface 的待执行步骤
+pub(crate) struct Planned {
+    pub(crate) step_id: String,
+    pub(crate) step: Step,
+    pub(crate) interface: Interface,
+}
+
+pub(crate) struct Runner {
+    client: Client,
+    base_url: String,
+    variables: HashMap<String, Value>,
+    headers: HashMap<String, String>,
+}
+
+impl Runner {
+    pub(crate) fn new(base_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            variables: HashMap::new(),
+            headers: HashMap::new(),
+        }
+    }
+
+    // 初始变量，通常来自运行环境
+    pub(crate) fn with_variables(mut self, variables: HashMap<String, Value>) -> Self {
+        self.variables = variables;
+        self
+    }
+
+    // 默认请求头，步骤中的同名请求头优先
+    pub(crate) fn with_headers(mut self, headers: HashMap<String, String>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    // setup 或 steps 失败后跳过剩余步骤，teardown 总是执行
+    pub(crate) async fn run(
+        &self,
+        setup: &[Planned],
+        steps: &[Planned],
+        teardown: &[Planned],
+    ) -> Vec<StepResult> {
+        let mut vars = self.variables.clone();
+        let mut results = Vec::new();
+        let mut failed = false;
+        for (stage, planned) in setup
+            .iter()
+            .map(|p| (Stage::Setup, p))
+            .chain(steps.iter().map(|p| (Stage::Step, p)))
+        {
+            if failed {
+                break;
+            }
+            let result = self.run_step(stage, planned, &mut vars).await;
+            failed = !result.passed;
+            results.push(result);
+        }
+        for planned in teardown {
+            results.push(self.run_step(Stage::Teardown, planned, &mut vars).await);
+        }
+        results
+    }
+
+    async fn run_step(
+        &self,
+        stage: Stage,
+        planned: &Planned,
+        vars: &mut HashMap<String, Value>,
+    ) -> StepResult {
+        let mut result = StepResult {
+            step_id: planned.step_id.clone(),
+            name: planned.step.name.clone(),
+            stage,
+            method: planned.interface.method.to_uppercase(),
+            url: format!("{}{}", self.base_url, planned.interface.url),
+            status: None,
+            latency: 0,
+            headers: HashMap::new(),
+            body: Value::Null,
+            error: None,
+            assertions: Vec::new(),
+            variables: HashMap::new(),
+            passed: false,
+        };
+
+        let req = match self.build_request(planned, vars) {
+            Ok(req) => req,
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+        result.url = req.url().to_string();
+
+        let start = Instant::now();
+        let mut res = match self.client.send(req).await {
+            Ok(res) => res,
+            Err(e) => {
+                result.latency = start.elapsed().as_millis() as u64;
+                result.error = Some(e.to_string());
+                return result;
+            }
+        };
+        let text = match res.body_string().await {
+            Ok(text) => text,
+            Err(e) => {
+                result.error = Some(e.to_string());
+                String::new()
+            }
+        };
+        result.latency = start.elapsed().as_millis() as u64;
+        result.status = Some(res.status() as u16);
+        result.headers = res
+            .iter()
+            .map(|(name, values)| (name.as_str().to_string(), values.as_str().to_string()))
+            .collect();
+        result.body = serde_json::from_str(&text).unwrap_or(Value::String(text));
+        if let Err(e) = extract(&planned.step, &mut result) {
+            result.error.get_or_insert(e);
+        }
+        vars.extend(result.variables.clone());
+        result.assertions = assertion::from_expect(&planned.step.expect)
+            .iter()
+            .chain(planned.step.assertions.iter())
+            .map(|a| assertion::evaluate(a, &result))
+            .collect();
+        result.passed = result.error.is_none() && result.assertions.iter().all(|a| a.passed);
+        result
+    }
+
+    fn build_request(
+        &self,
+        planned: &Planned,
+        vars: &HashMap<String, Value>,
+    ) -> Result<surf::Request, String> {
+        let Planned { step, interface, .. } = planned;
+        let method: Method = interface
+            .method
+            .to_uppercase()
+            .parse()
+            .map_err(|e: surf::Error| e.to_string())?;
+        let path = template::render_str(&interface.url, vars)?;
+        let mut url =
+            Url::parse(&format!("{}{}", self.base_url, path)).map_err(|e| e.to_string())?;
+
+        let param = template::render_map(&pick_fields(&interface.param, &step.param), vars)?;
+        if !param.is_empty() {
+            let mut pairs = url.query_pairs_mut();
+            for (k, v) in param.iter() {
+                match v {
+                    Value::String(s) => pairs.append_pair(k, s),
+                    v => pairs.append_pair(k, &v.to_string()),
+                };
+            }
+        }
+
+        let mut req = surf::Request::new(method, url);
+        for (k, v) in self.headers.iter().chain(step.headers.iter()) {
+            req.insert_header(k.as_str(), template::render_str(v, vars)?.as_str());
+        }
+        let data = match &step.data {
+            Value::Object(data) => Value::Object(pick_fields(&interface.data, data)),
+            data => data.clone(),
+        };
+        if !data.is_null() {
+            req.body_json(&template::render_value(&data, vars)?)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(req)
+    }
+}
+
+// 按 Step.extract 从响应中取值，写入 result.variables
+fn extract(step: &Step, result: &mut StepResult) -> Result<(), String> {
+    for item in step.extract.iter() {
+        let value = match item.from {
+            Source::Body => json_path::select(&result.body, &item.path).cloned(),
+            Source::Header => result
+                .headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(&item.path))
+                .map(|(_, v)| Value::from(v.as_str())),
+        };
+        match value {
+            Some(v) => result.variables.insert(item.name.clone(), v),
+            None => return Err(format!("extract {} from {} failed", item.name, item.path)),
+        };
+    }
+    Ok(())
+}
+
+// 只发送 Interface 中定义过的字段，未定义字段时原样发送
+fn pick_fields(fields: &[Field], values: &Map<String, Value>) -> Map<String, Value> {
+    if fields.is_empty() {
+        return values.clone();
+    }
+    values
+        .iter()
+        .filter(|(k, _)| fields.iter().any(|f| &f.name == *k))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect()
+}
+
+// 执行记录会保存下来，把密文变量的明文替换成 SECRET_MASK，url 中的值按编码后的形式匹配
+pub(crate) fn mask_secrets(results: &mut [StepResult], secrets: &[String]) {
+    let mut patterns = Vec::new();
+    for secret in secrets.iter().filter(|s| !s.is_empty()) {
+        patterns.push(secret.clone());
+        patterns.extend(url_encoded(secret));
+    }
+    // 先替换长的，避免一个值是另一个的子串时只替换了一部分
+    patterns.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+    patterns.dedup();
+    if patterns.is_empty() {
+        return;
+    }
+
+    for result in results.iter_mut() {
+        mask_str(&mut result.url, &patterns);
+        if let Some(error) = result.error.as_mut() {
+            mask_str(error, &patterns);
+        }
+        for v in result.headers.values_mut() {
+            mask_str(v, &patterns);
+        }
+        mask_value(&mut result.body, &patterns);
+        for v in result.variables.values_mut() {
+            mask_value(v, &patterns);
+        }
+        for a in result.assertions.iter_mut() {
+            mask_value(&mut a.expected, &patterns);
+            mask_value(&mut a.actual, &patterns);
+            if let Some(message) = a.message.as_mut() {
+                mask_str(message, &patterns);
+            }
+        }
+    }
+}
+
+// 值放在查询参数和路径中时编码后的形式
+fn url_encoded(value: &str) -> Vec<String> {
+    let mut url = match Url::parse("http://mask/") {
+        Ok(url) => url,
+        Err(_) => return Vec::new(),
+    };
+    let mut res = Vec::new();
+    url.query_pairs_mut().append_pair("v", value);
+    res.extend(url.query().map(|q| q.trim_start_matches("v=").to_string()));
+    url.set_query(None);
+    url.set_path(value);
+    res.push(url.path().trim_start_matches('/').to_string());
+    res.retain(|v| !v.is_empty() && v != value);
+    res
+}
+
+fn mask_str(value: &mut String, patterns: &[String]) {
+    for p in patterns {
+        if value.contains(p.as_str()) {
+            *value = value.replace(p.as_str(), SECRET_MASK);
+        }
+    }
+}
+
+fn mask_value(value: &mut Value, patterns: &[String]) {
+    match value {
+        Value::String(s) => mask_str(s, patterns),
+        Value::Array(items) => items.iter_mut().for_each(|v| mask_value(v, patterns)),
+        Value::Object(map) => map.values_mut().for_each(|v| mask_value(v, patterns)),
+        _ => {}
+    }
+}
+
+// expected 中的字段都能在 actual 中找到且相等
+pub(crate) fn json_contains(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Object(a), Value::Object(e)) => e
+            .iter()
+            .all(|(k, v)| a.get(k).map_or(false, |av| json_contains(av, v))),
+        (Value::Array(a), Value::Array(e)) => e
+            .iter()
+            .all(|ev| a.iter().any(|av| json_contains(av, ev))),
+        (a, e) => a == e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use async_std::net::TcpListener;
+    use chrono::Local;
+    use serde_json::{json, Map};
+    use tide::{Request, StatusCode};
+
+    use super::{json_contains, mask_secrets, Planned, Runner};
+    use crate::models::{Expect, Extract, Field, Interface, Source, Stage, Step, SECRET_MASK};
+
+    fn interface(url: &str, method: &str, param: Vec<Field>) -> Interface {
+        Interface {
+            url: url.to_string(),
+            description: String::new(),
+            module: String::from("test"),
+            method: method.to_string(),
+            data: Vec::new(),
+            param,
+            response: Vec::new(),
+            example: None,
+            project_id: String::new(),
+            user_id: String::new(),
+            create_at: Local::now(),
+            update_at: None,
+        }
+    }
+
+    fn step(name: &str, param: Map<String, serde_json::Value>, expect: Expect) -> Step {
+        Step {
+            name: name.to_string(),
+            interface_id: String::new(),
+            param,
+            data: json!({ "name": "lomect" }),
+            headers: HashMap::new(),
+            expect,
+            assertions: Vec::new(),
+            extract: Vec::new(),
+            project_id: String::new(),
+            user_id: String::new(),
+            create_at: Local::now(),
+            update_at: None,
+        }
+    }
+
+    async fn echo(mut req: Request<()>) -> tide::Result {
+        let body: serde_json::Value = req.body_json().await?;
+        let query = req.url().query().unwrap_or("").to_string();
+        let auth = req.header("Authorization").map(|h| h.as_str().to_string());
+        Ok(json!({ "query": query, "body": body, "auth": auth }).into())
+    }
+
+    async fn missing(_req: Request<()>) -> tide::Result {
+        Ok(StatusCode::NotFound.into())
+    }
+
+    // 监听随机端口，返回服务地址
+    async fn stub_server() -> String {
+        let mut app = tide::new();
+        app.at("/echo").post(echo);
+        app.at("/missing").get(missing);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        async_std::task::spawn(app.listen(listener));
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn test_json_contains() {
+        let actual = json!({ "code": 1000, "data": { "id": 1, "tags": ["a", "b"] } });
+        assert!(json_contains(&actual, &json!({ "code": 1000 })));
+        assert!(json_contains(&actual, &json!({ "data": { "tags": ["b"] } })));
+        assert!(!json_contains(&actual, &json!({ "data": { "id": 2 } })));
+    }
+
+    #[async_std::test]
+    async fn test_run_case() {
+        let base = stub_server().await;
+        let field = Field {
+            name: String::from("page"),
+            required: true,
+            data_type: String::from("integer"),
+            max: None,
+            min: None,
+            length_min: None,
+            length_max: None,
+            fields: Vec::new(),
+            items: None,
+        };
+        let mut param = Map::new();
+        param.insert(String::from("page"), json!(1));
+        param.insert(String::from("ignored"), json!("x"));
+
+        let echo_step = Planned {
+            step_id: String::from("1"),
+            step: step(
+                "echo",
+                param,
+                Expect {
+                    status: Some(200),
+                    body: Some(json!({ "body": { "name": "lomect" } })),
+                },
+            ),
+            interface: interface("/echo", "post", vec![field]),
+        };
+        let not_found = Planned {
+            step_id: String::from("2"),
+            step: step("missing", Map::new(), Expect { status: Some(200), body: None }),
+            interface: interface("/missing", "GET", Vec::new()),
+        };
+        let skipped = Planned {
+            step_id: String::from("3"),
+            step: step("skipped", Map::new(), Expect::default()),
+            interface: interface("/echo", "POST", Vec::new()),
+        };
+        let teardown = Planned {
+            step_id: String::from("4"),
+            step: step("teardown", Map::new(), Expect::default()),
+            interface: interface("/echo", "POST", Vec::new()),
+        };
+
+        let runner = Runner::new(&format!("{}/", base));
+        let results = runner
+            .run(&[echo_step], &[not_found, skipped], &[teardown])
+            .await;
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].passed);
+        assert_eq!(results[0].body["query"], json!("page=1"));
+        assert_eq!(results[1].status, Some(404));
+        assert!(!results[1].passed);
+        assert_eq!(results[2].stage, Stage::Teardown);
+        assert!(results[2].passed);
+    }
+
+    #[async_std::test]
+    async fn test_run_with_variables() {
+        let base = stub_server().await;
+        let mut login = step("login", Map::new(), Expect::default());
+        login.data = json!({ "name": "{{$randomEmail}}" });
+        login.extract = vec![
+            Extract {
+                name: String::from("token"),
+                from: Source::Body,
+                path: String::from("$.body.name"),
+            },
+            Extract {
+                name: String::from("type"),
+                from: Source::Header,
+                path: String::from("Content-Type"),
+            },
+        ];
+        let mut info = step("info", Map::new(), Expect::default());
+        info.headers
+            .insert(String::from("Authorization"), String::from("Bearer {{token}}"));
+        info.data = json!({ "type": "{{type}}" });
+        let mut broken = step("broken", Map::new(), Expect::default());
+        broken.data = json!({ "missing": "{{nope}}" });
+
+        let plan = |name: &str, step: Step| Planned {
+            step_id: name.to_string(),
+            step,
+            interface: interface("/echo", "POST", Vec::new()),
+        };
+        let mut headers = HashMap::new();
+        headers.insert(String::from("Authorization"), String::from("none"));
+        let runner = Runner::new(&base).with_headers(headers);
+        let results = runner
+            .run(&[plan("1", login)], &[plan("2", info)], &[plan("3", broken)])
+            .await;
+
+        assert_eq!(results.len(), 3);
+        let token = results[0].variables["token"].as_str().unwrap();
+        assert!(token.ends_with("@example.com"));
+        assert_eq!(results[1].body["auth"], json!(format!("Bearer {}", token)));
+        assert_eq!(results[1].body["body"]["type"], results[0].variables["type"]);
+        assert!(results[1].passed);
+        assert!(results[2].error.as_ref().unwrap().contains("nope"));
+        assert!(!results[2].passed);
+
+        let mut variables = HashMap::new();
+        variables.insert(String::from("nope"), json!("env"));
+        let runner = Runner::new(&base).with_variables(variables);
+        let mut broken = step("broken", Map::new(), Expect::default());
+        broken.data = json!({ "missing": "{{nope}}" });
+        let results = runner.run(&[], &[plan("1", broken)], &[]).await;
+        assert_eq!(results[0].body["body"]["missing"], json!("env"));
+        assert_eq!(results[0].body["auth"], json!(null));
+    }
+
+    // 密文变量出现在查询参数中时，执行记录里只保存掩码
+    #[async_std::test]
+    async fn test_mask_secrets() {
+        let base = stub_server().await;
+        let secret = "s3cr et&/key";
+        let mut variables = HashMap::new();
+        variables.insert(String::from("key"), json!(secret));
+        let mut param = Map::new();
+        param.insert(String::from("key"), json!("{{key}}"));
+        let mut echo = step("echo", param, Expect::default());
+        echo.data = json!({ "key": "{{key}}" });
+        let planned = Planned {
+            step_id: String::from("1"),
+            step: echo,
+            interface: interface("/echo", "POST", Vec::new()),
+        };
+
+        let runner = Runner::new(&base).with_variables(variables);
+        let mut results = runner.run(&[], &[planned], &[]).await;
+        assert!(results[0].passed);
+        assert_eq!(results[0].body["body"]["key"], json!(secret));
+
+        mask_secrets(&mut results, &[String::from(secret)]);
+        let saved = serde_json::to_string(&results).unwrap();
+        assert!(!saved.contains("s3cr"));
+        assert!(results[0].url.ends_with(&format!("?key={}", SECRET_MASK)));
+        assert_eq!(results[0].body["query"], json!(format!("key={}", SECRET_MASK)));
+        assert_eq!(results[0].body["body"]["key"], json!(SECRET_MASK));
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use chrono::prelude::Local;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::utils::rand_str;
+
+// 内置变量，每次引用都会重新生成
+fn builtin(name: &str) -> Option<Value> {
+    match name {
+        "$timestamp" => Some(Value::from(Local::now().timestamp())),
+        "$uuid" => Some(Value::from(Uuid::new_v4().to_string())),
+        "$randomEmail" => Some(Value::from(format!(
+            "{}@example.com",
+            rand_str(10).to_lowercase()
+        ))),
+        _ => None,
+    }
+}
+
+fn lookup(name: &str, vars: &HashMap<String, Value>) -> Result<Value, String> {
+    match builtin(name).or_else(|| vars.get(name).cloned()) {
+        Some(v) => Ok(v),
+        None => Err(format!("variable {} not defined", name)),
+    }
+}
+
+fn to_text(value: Value) -> String {
+    match value {
+        Value::String(s) => s,
+        v => v.to_string(),
+    }
+}
+
+// 替换字符串中的 {{var}}，整个字符串只有一个占位符时保留变量原来的类型
+pub(crate) fn render(s: &str, vars: &HashMap<String, Value>) -> Result<Value, String> {
+    let trimmed = s.trim();
+    if trimmed.starts_with("{{") && trimmed.ends_with("}}") && trimmed.matches("{{").count() == 1
+    {
+        return lookup(trimmed[2..trimmed.len() - 2].trim(), vars);
+    }
+    render_str(s, vars).map(Value::String)
+}
+
+pub(crate) fn render_str(s: &str, vars: &HashMap<String, Value>) -> Result<String, String> {
+    let mut res = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+        res.push_str(&rest[..start]);
+        res.push_str(&to_text(lookup(rest[start + 2..end].trim(), vars)?));
+        rest = &rest[end + 2..];
+    }
+    res.push_str(rest);
+    Ok(res)
+}
+
+pub(crate) fn render_value(value: &Value, vars: &HashMap<String, Value>) -> Result<Value, String> {
+    match value {
+        Value::String(s) => render(s, vars),
+        Value::Array(list) => list
+            .iter()
+            .map(|v| render_value(v, vars))
+            .collect::<Result<Vec<Value>, String>>()
+            .map(Value::Array),
+        Value::Object(map) => render_map(map, vars).map(Value::Object),
+        v => Ok(v.clone()),
+    }
+}
+
+pub(crate) fn render_map(
+    map: &Map<String, Value>,
+    vars: &HashMap<String, Value>,
+) -> Result<Map<String, Value>, String> {
+    let mut res = Map::new();
+    for (k, v) in map.iter() {
+        res.insert(k.clone(), render_value(v, vars)?);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::{json, Value};
+
+    use super::{render, render_str, render_value};
+
+    #[test]
+    fn test_render() {
+        let mut vars = HashMap::new();
+        vars.insert(String::from("token"), json!("abc"));
+        vars.insert(String::from("id"), json!(12));
+
+        assert_eq!(render_str("Bearer {{ token }}", &vars), Ok(String::from("Bearer abc")));
+        assert_eq!(render_str("/user/{{id}}/info", &vars), Ok(String::from("/user/12/info")));
+        assert_eq!(render("{{id}}", &vars), Ok(json!(12)));
+        assert!(render("{{missing}}", &vars).is_err());
+        assert_eq!(render_str("{{ not closed", &vars), Ok(String::from("{{ not closed")));
+
+        let body = render_value(&json!({ "id": "{{id}}", "list": ["{{token}}"] }), &vars).unwrap();
+        assert_eq!(body, json!({ "id": 12, "list": ["abc"] }));
+
+        let builtin = render_value(&json!(["{{$timestamp}}", "{{$uuid}}", "{{$randomEmail}}"]), &vars)
+            .unwrap();
+        assert!(builtin[0].is_i64());
+        assert_eq!(builtin[1].as_str().map(|s| s.len()), Some(36));
+        assert!(builtin[2].as_str().map_or(false, |s| s.ends_with("@example.com")));
+        assert_ne!(render("{{$uuid}}", &vars), render("{{$uuid}}", &vars));
+        assert_eq!(render("", &vars), Ok(Value::String(String::new())));
+    }
+}
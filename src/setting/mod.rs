@@ -1,3 +1,3 @@
-mod setting;
+pub(crate) mod setting;
 
-pub use setting::Setting;
\ No newline at end of file
+pub use setting::{Argon2, Jwt, OidcProvider, PasswordPolicy, Setting};
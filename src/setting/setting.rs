@@ -1,3 +1,5 @@
+use std::net::IpAddr;
+
 use config::{Config, ConfigError, File};
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -10,7 +12,10 @@ pub struct Database {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Server {
     pub server: String,
-    pub domain: String
+    pub domain: String,
+    // 前面的反向代理的 IP，只有这些地址发来的 X-Forwarded-For 才可信
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -20,11 +25,119 @@ pub struct Email {
     pub email_server: String,
 }
 
+// 不使用密文变量和两步验证时可以不配置
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Secret {
+    pub secret_key: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct JwtKey {
+    pub kid: String,
+    // HS256 或 EdDSA
+    pub algorithm: String,
+    pub secret: Option<String>,
+    // EdDSA 使用 PEM 格式，已轮换掉的 key 可以只保留公钥
+    pub private_key: Option<String>,
+    pub public_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Jwt {
+    // 开启后校验 access token 只查 redis 黑名单，session 的最后活跃时间精确到 token 刷新
+    pub enabled: bool,
+    // 签发使用的 key，其余 key 只用于校验
+    pub kid: String,
+    pub keys: Vec<JwtKey>,
+}
+
+// Argon2id 参数，调整后旧的 hash 在登录成功时自动升级
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Argon2 {
+    pub mem_cost: u32,
+    pub time_cost: u32,
+    pub lanes: u32,
+    pub hash_len: u32,
+    pub salt_len: usize,
+}
+
+impl Default for Argon2 {
+    fn default() -> Self {
+        Argon2 {
+            mem_cost: 4096,
+            time_cost: 10,
+            lanes: 4,
+            hash_len: 32,
+            salt_len: 16,
+        }
+    }
+}
+
+// 密码策略，history 为不能与最近几次使用过的密码相同，0 表示不检查
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_lower: bool,
+    pub require_upper: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    pub history: usize,
+    // 额外的弱密码列表，每行一个，与内置列表合并
+    pub breached_file: Option<String>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            require_lower: true,
+            require_upper: true,
+            require_digit: true,
+            require_symbol: false,
+            history: 5,
+            breached_file: None,
+        }
+    }
+}
+
+// OpenID Connect 登录方式，按 name 区分，如 /auth/oidc/google
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OidcProvider {
+    pub name: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub authorize_url: String,
+    pub token_url: String,
+    pub userinfo_url: String,
+    // 前端回调地址，需要与身份提供方登记的一致
+    pub redirect_uri: String,
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+}
+
+fn default_scopes() -> Vec<String> {
+    vec!["openid".into(), "email".into(), "profile".into()]
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Setting {
     pub database: Database,
     pub server: Server,
     pub email: Email,
+    #[serde(default)]
+    pub secret: Secret,
+    #[serde(default)]
+    pub jwt: Jwt,
+    #[serde(default)]
+    pub argon2: Argon2,
+    #[serde(default)]
+    pub password: PasswordPolicy,
+    #[serde(default)]
+    pub oidc: Vec<OidcProvider>,
+    // 这些邮箱的帐号为管理员，启动时已有的帐号也会设置
+    #[serde(default)]
+    pub admin_emails: Vec<String>,
     pub env: String
 }
 
@@ -39,5 +152,13 @@ impl Setting {
         s.merge(File::with_name(&filename))?;
         s.try_into()
     }
+
+    pub fn oidc_provider(&self, name: &str) -> Option<&OidcProvider> {
+        self.oidc.iter().find(|p| p.name == name)
+    }
+
+    pub fn is_admin_email(&self, email: &str) -> bool {
+        self.admin_emails.iter().any(|e| e.eq_ignore_ascii_case(email))
+    }
 }
 
@@ -1,4 +1,8 @@
-use crate::db::{MongoDb, Redis};
+use tide::StatusCode;
+
+use crate::db::{migrate_default_projects, promote_admins, MongoDb, Redis};
+use crate::models::{IDENTITY, USER};
+use crate::utils::{check_argon2, check_secret_key, KeySet, KEYSET};
 use crate::CONFIG;
 
 #[derive(Clone)]
@@ -9,7 +13,19 @@ pub struct State {
 
 impl State {
     pub async fn new() -> tide::Result<Self> {
+        // 配置错误时拒绝启动，不要等到处理请求时才 panic
+        let config_error = |e: String| tide::Error::from_str(StatusCode::InternalServerError, e);
+        check_secret_key().map_err(config_error)?;
+        check_argon2().map_err(config_error)?;
+        KeySet::new(&CONFIG.jwt).map_err(config_error)?;
+        lazy_static::initialize(&KEYSET);
         let mongc = MongoDb::new(&CONFIG.database.mongo_url, &CONFIG.database.mongo_name).await?;
+        // 邮箱唯一，注册和修改邮箱时由数据库保证不重复
+        mongc.create_unique_index(&USER, &["email"]).await?;
+        // 同一个第三方帐号只能绑定一次
+        mongc.create_unique_index(&IDENTITY, &["provider", "subject"]).await?;
+        promote_admins(&mongc, &CONFIG.admin_emails).await?;
+        migrate_default_projects(&mongc).await?;
         let redic = Redis::new(&CONFIG.database.redis_url)?;
         Ok(State {
             mongo: mongc,
@@ -0,0 +1,19 @@
+mod routers;
+mod schema;
+
+use tide::Server;
+
+use crate::State;
+use routers::{add_step, delete_step, get_step, list_step, update_step};
+use crate::middleware::{guard, LoginMiddleware, ProjectMiddleware};
+
+pub(crate) fn step_router(app: &mut Server<State>) {
+    let mut step = app.at("/project/:pid/step");
+    step.with(LoginMiddleware).with(ProjectMiddleware);
+    step.at("/").get(guard("step:read", list_step));
+    step.at("/add").post(guard("step:write", add_step));
+    step.at("/:id")
+        .get(guard("step:read", get_step))
+        .put(guard("step:write", update_step))
+        .delete(guard("step:write", delete_step));
+}
@@ -0,0 +1,121 @@
+use mongodb::bson::{doc, from_document, oid::ObjectId, to_bson, Document};
+use tide::Request;
+use validator::Validate;
+
+use super::schema::{AddStep, GetStep, ResStep};
+use crate::db::{MongoDb, Options};
+use crate::middleware::CurrentUser;
+use crate::models::{Step, INTERFACE, STEP};
+use crate::runner::verify_assertions;
+use crate::utils::*;
+use crate::State;
+
+fn to_res_step(data: Document) -> Option<ResStep> {
+    let id = data.get_object_id("_id").ok()?.to_hex();
+    let step = from_document::<Step>(data).ok()?;
+    Some(ResStep { id, step })
+}
+
+// 步骤只能引用同一项目的接口
+async fn interface_exist(mongo: &MongoDb, project_id: &str, id: &str) -> tide::Result<bool> {
+    let oid = match ObjectId::with_string(id) {
+        Ok(oid) => oid,
+        Err(_) => return Ok(false),
+    };
+    let mut opt = Options::default();
+    opt.find_one_opt(&INTERFACE, Some(doc! { "_id": oid, "project_id": project_id }));
+    Ok(!mongo.find(opt).await?.is_empty())
+}
+
+pub(crate) async fn add_step(mut req: Request<State>) -> tide::Result {
+    let data: AddStep = match req.body_json().await {
+        Ok(data) => data,
+        Err(error) => {
+            return Responser::new(Some(format!("{}", error)), &status::BAD_REQUEST).to_result()
+        }
+    };
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let errors = verify_assertions(&data.assertions);
+    if !errors.is_empty() {
+        return Responser::new(Some(errors), &status::BAD_REQUEST).to_result();
+    }
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let project_id = req.param::<String>("pid")?;
+    let mongo = &req.state().mongo;
+    if !interface_exist(mongo, &project_id, &data.interface_id).await? {
+        return Responser::new(Some("接口不存在"), &status::BAD_REQUEST).to_result();
+    }
+
+    let step = data.into_step(project_id, user_id);
+    let mut opt = Options::default();
+    opt.set_collect(&STEP);
+    let id = mongo.insert_one(opt, &step).await?;
+    Responser::new(Some(ResStep { id, step }), &status::OK).to_result()
+}
+
+pub(crate) async fn get_step(req: Request<State>) -> tide::Result {
+    crud::get_one(&req, &STEP, to_res_step, "步骤不存在").await
+}
+
+pub(crate) async fn list_step(req: Request<State>) -> tide::Result {
+    let filter: GetStep = match req.query() {
+        Ok(res) => res,
+        Err(e) => return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result(),
+    };
+
+    let mut condition = doc! { "project_id": req.param::<String>("pid")? };
+    if let Some(name) = filter.name {
+        condition.insert("name", name);
+    }
+    if let Some(interface_id) = filter.interface_id {
+        condition.insert("interface_id", interface_id);
+    }
+
+    crud::list(&req, &STEP, condition, &filter.page, to_res_step).await
+}
+
+pub(crate) async fn update_step(mut req: Request<State>) -> tide::Result {
+    let data: AddStep = match req.body_json().await {
+        Ok(data) => data,
+        Err(error) => {
+            return Responser::new(Some(format!("{}", error)), &status::BAD_REQUEST).to_result()
+        }
+    };
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let errors = verify_assertions(&data.assertions);
+    if !errors.is_empty() {
+        return Responser::new(Some(errors), &status::BAD_REQUEST).to_result();
+    }
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let project_id = req.param::<String>("pid")?;
+    if !interface_exist(&req.state().mongo, &project_id, &data.interface_id).await? {
+        return Responser::new(Some("接口不存在"), &status::BAD_REQUEST).to_result();
+    }
+
+    let step = data.into_step(project_id, user_id);
+    let set = doc! {
+        "name": &step.name,
+        "interface_id": &step.interface_id,
+        "param": to_bson(&step.param)?,
+        "data": to_bson(&step.data)?,
+        "headers": to_bson(&step.headers)?,
+        "expect": to_bson(&step.expect)?,
+        "assertions": to_bson(&step.assertions)?,
+        "extract": to_bson(&step.extract)?,
+    };
+    crud::update_one(&req, &STEP, set, "步骤不存在").await
+}
+
+pub(crate) async fn delete_step(req: Request<State>) -> tide::Result {
+    crud::delete_one(&req, &STEP, "步骤不存在").await
+}
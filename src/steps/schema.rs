@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use chrono::prelude::Local;
+use serde_json::{Map, Value};
+use validator::Validate;
+
+use crate::models::{Assertion, Expect, Extract, Step};
+use crate::utils::Page;
+
+#[derive(Deserialize, Validate)]
+pub(crate) struct AddStep {
+    #[validate(length(min = 1, message = "name can not be empty"))]
+    pub(crate) name: String,
+    #[validate(length(equal = 24, message = "interface_id error"))]
+    pub(crate) interface_id: String,
+    #[serde(default)]
+    pub(crate) param: Map<String, Value>,
+    #[serde(default)]
+    pub(crate) data: Value,
+    #[serde(default)]
+    pub(crate) headers: HashMap<String, String>,
+    #[serde(default)]
+    pub(crate) expect: Expect,
+    #[serde(default)]
+    pub(crate) assertions: Vec<Assertion>,
+    #[serde(default)]
+    pub(crate) extract: Vec<Extract>,
+}
+
+impl AddStep {
+    pub(crate) fn into_step(self, project_id: String, user_id: String) -> Step {
+        Step {
+            name: self.name,
+            interface_id: self.interface_id,
+            param: self.param,
+            data: self.data,
+            headers: self.headers,
+            expect: self.expect,
+            assertions: self.assertions,
+            extract: self.extract,
+            project_id,
+            user_id,
+            create_at: Local::now(),
+            update_at: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub(crate) struct GetStep {
+    pub(crate) name: Option<String>,
+    pub(crate) interface_id: Option<String>,
+    pub(crate) page: Page,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ResStep {
+    pub(crate) id: String,
+    #[serde(flatten)]
+    pub(crate) step: Step,
+}
@@ -4,10 +4,23 @@ mod schema;
 use tide::Server;
 
 use crate::State;
-use crate::middleware::LoginMiddleware;
+use crate::middleware::{guard, LoginMiddleware};
 
 pub fn user_router(api: &mut Server<State>) {
-    let mut user = api.with(LoginMiddleware).at("/user");
-    user.at("/").get(routers::get_user);
+    let mut user = api.at("/user");
+    user.with(LoginMiddleware);
+    user.at("/").get(guard("user:read", routers::get_user));
+    user.at("/:id/roles").put(guard("user:admin", routers::set_roles));
+    user.at("/email").post(routers::change_email);
+    user.at("/sessions").get(routers::list_sessions);
+    user.at("/sessions/:id").delete(routers::delete_session);
+    user.at("/keys")
+        .get(routers::list_keys)
+        .post(routers::add_key);
+    user.at("/keys/:id").delete(routers::delete_key);
+    user.at("/mfa/enroll").post(routers::mfa_enroll);
+    user.at("/mfa/confirm").post(routers::mfa_confirm);
+    user.at("/mfa/disable").post(routers::mfa_disable);
+    user.at("/mfa/recovery").post(routers::mfa_recovery);
 }
 
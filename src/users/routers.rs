@@ -1,23 +1,34 @@
-use super::schema::{GetUser, ResUser};
+use super::schema::{
+    AddApiKey, ChangeEmail, GetUser, MfaCode, ResApiKey, ResMfaEnroll, ResNewApiKey, ResUser,
+    SetRoles,
+};
 use crate::{
-    db::Options,
-    models::USER,
-    utils::{status, Responser},
-    State,
+    auth::verify_mfa,
+    db::{find_user, MongoDb, Options, CONFIRM_EXPIRE_TIME, EMAIL_CANCEL_TIME},
+    middleware::{bearer, session_owner, CurrentUser},
+    models::{ApiKey, EmailChange, Mfa, Role, API_KEY, KEY_PREFIX, USER},
+    utils::{
+        encrypt, hash_password, password_verify, rand_str, send_email, status, totp, Responser,
+    },
+    State, CONFIG,
+};
+use async_std::task;
+use chrono::{
+    prelude::{Local, Utc},
+    Duration,
 };
-use mongodb::bson::{doc, from_document};
+use mongodb::bson::{doc, from_document, oid::ObjectId, to_bson, Document};
 use tide::log;
+use validator::Validate;
 
 pub async fn get_user(mut req: tide::Request<State>) -> tide::Result {
-    log::error!("get user");
     let filter: GetUser = match req.query() {
         Ok(res) => res,
         Err(e) => return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result(),
     };
-    log::error!("filter {:?}", filter);
 
     let skip = (filter.page.page_num - 1) * filter.page.page_size;
-    let fileds = Some(doc! {"username": 1, "email": 1, "phone": 1, "create_at": 1});
+    let fileds = Some(doc! {"username": 1, "email": 1, "phone": 1, "roles": 1, "create_at": 1});
     let mut opt = Options::new(
         &USER,
         None,
@@ -37,8 +48,332 @@ pub async fn get_user(mut req: tide::Request<State>) -> tide::Result {
     };
     let data: Vec<Option<ResUser>> = users
         .into_iter()
-        .map(|s| from_document::<ResUser>(s).ok())
+        .map(|s| {
+            let id = s.get_object_id("_id").ok()?.to_hex();
+            let user = from_document::<ResUser>(s).ok()?;
+            Some(ResUser { id, ..user })
+        })
         .filter(|s| s.is_some())
         .collect();
     Responser::new(Some(data), &status::OK).to_result()
 }
+
+pub async fn list_sessions(req: tide::Request<State>) -> tide::Result {
+    let user_id = match session_owner(&req) {
+        Some(id) => id,
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let access = bearer(&req);
+    let sessions = req.state().redis.sessions(&user_id, access.as_deref()).await?;
+    Responser::new(Some(sessions), &status::OK).to_result()
+}
+
+pub async fn delete_session(req: tide::Request<State>) -> tide::Result {
+    let user_id = match session_owner(&req) {
+        Some(id) => id,
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let id = req.param::<String>("id")?;
+    if !req.state().redis.revoke_session(&user_id, &id).await? {
+        return Responser::new(Some("session 不存在"), &status::BAD_REQUEST).to_result();
+    }
+    Responser::new(Some(id), &status::OK).to_result()
+}
+
+pub async fn set_roles(mut req: tide::Request<State>) -> tide::Result {
+    let data: SetRoles = req.body_json().await?;
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let user_id = match req.ext::<CurrentUser>() {
+        Some(u) => u.id.clone(),
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let id = req.param::<String>("id")?;
+    let oid = match ObjectId::with_string(&id) {
+        Ok(oid) => oid,
+        Err(e) => return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result(),
+    };
+    // 防止管理员把自己降级后无人可以管理
+    if id == user_id && !data.roles.contains(&Role::Admin) {
+        return Responser::new(Some("不能移除自己的管理员权限"), &status::BAD_REQUEST).to_result();
+    }
+
+    let mut opt = Options::default();
+    opt.update_opt(&USER, Some(doc! { "_id": oid }), None);
+    let update = doc! { "$set": { "roles": to_bson(&data.roles)? } };
+    let count = req.state().mongo.update(update, opt).await?;
+    if count == 0 {
+        return Responser::new(Some("帐号不存在"), &status::BAD_REQUEST).to_result();
+    }
+    Responser::new(Some(id), &status::OK).to_result()
+}
+
+pub async fn list_keys(req: tide::Request<State>) -> tide::Result {
+    let user_id = match session_owner(&req) {
+        Some(id) => id,
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let opt = Options::new(
+        &API_KEY,
+        Some(doc! { "user_id": user_id }),
+        None,
+        None,
+        Some(doc! { "_id": -1 }),
+        None,
+    );
+    let keys: Vec<ResApiKey> = req
+        .state()
+        .mongo
+        .find(opt)
+        .await?
+        .into_iter()
+        .filter_map(|d| {
+            let id = d.get_object_id("_id").ok()?.to_hex();
+            Some(ResApiKey::new(id, from_document::<ApiKey>(d).ok()?))
+        })
+        .collect();
+    Responser::new(Some(keys), &status::OK).to_result()
+}
+
+pub async fn add_key(mut req: tide::Request<State>) -> tide::Result {
+    let data: AddApiKey = req.body_json().await?;
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let user_id = match session_owner(&req) {
+        Some(id) => id,
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+
+    let prefix = rand_str(8);
+    let secret = rand_str(32);
+    let api_key = ApiKey {
+        name: data.name,
+        prefix: prefix.clone(),
+        hash: hash_password(&secret)?,
+        scopes: data.scopes,
+        user_id,
+        expire_at: data.expire_days.map(|d| Local::now() + Duration::days(d)),
+        last_used: None,
+        create_at: Local::now(),
+    };
+    let mut opt = Options::default();
+    opt.set_collect(&API_KEY);
+    let id = req.state().mongo.insert_one(opt, &api_key).await?;
+    let res = ResNewApiKey {
+        key: format!("{}{}_{}", KEY_PREFIX, prefix, secret),
+        info: ResApiKey::new(id, api_key),
+    };
+    Responser::new(Some(res), &status::OK).to_result()
+}
+
+pub async fn delete_key(req: tide::Request<State>) -> tide::Result {
+    let user_id = match session_owner(&req) {
+        Some(id) => id,
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let id = req.param::<String>("id")?;
+    let oid = match ObjectId::with_string(&id) {
+        Ok(oid) => oid,
+        Err(e) => return Responser::new(Some(e.to_string()), &status::BAD_REQUEST).to_result(),
+    };
+
+    let mut opt = Options::default();
+    opt.del_opt(&API_KEY, Some(doc! { "_id": oid, "user_id": user_id }), None);
+    let count = req.state().mongo.delete(opt).await?;
+    if count == 0 {
+        return Responser::new(Some("API key 不存在"), &status::BAD_REQUEST).to_result();
+    }
+    Responser::new(Some(id), &status::OK).to_result()
+}
+
+// 新邮箱收到确认链接，旧邮箱收到撤销链接
+pub async fn change_email(mut req: tide::Request<State>) -> tide::Result {
+    let mut data: ChangeEmail = req.body_json().await?;
+    data.email = data.email.to_lowercase();
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let id = match session_owner(&req) {
+        Some(id) => id,
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let mongo = &req.state().mongo;
+    let user = match find_user(mongo, &id).await? {
+        Some(user) => user,
+        None => return Responser::new(Some("帐号不存在"), &status::BAD_REQUEST).to_result(),
+    };
+    if !password_verify(&user.password, &data.password) {
+        return Responser::new(Some("密码错误"), &status::BAD_REQUEST).to_result();
+    }
+    if data.email == user.email {
+        return Responser::new(Some("新邮箱与当前邮箱相同"), &status::BAD_REQUEST).to_result();
+    }
+    let now = Local::now();
+    if let Some(change) = &user.email_change {
+        if change.locked(now, *EMAIL_CANCEL_TIME) {
+            let msg = "上一次修改的邮箱还在撤销期内，请稍后再试";
+            return Responser::new(Some(msg), &status::BAD_REQUEST).to_result();
+        }
+    }
+    // 这里只是提前提示，确认时由唯一索引保证
+    let mut opt = Options::default();
+    opt.find_one_opt(&USER, Some(doc! { "email": &data.email }));
+    if !mongo.find(opt).await?.is_empty() {
+        return Responser::new(Some("邮箱已被使用"), &status::BAD_REQUEST).to_result();
+    }
+
+    let change = EmailChange {
+        old_email: user.email.clone(),
+        new_email: data.email.clone(),
+        request_at: now,
+        confirm_at: None,
+    };
+    update_user(mongo, &id, doc! { "$set": { "email_change": to_bson(&change)? } }).await?;
+
+    let redis_cli = &req.state().redis;
+    let token = redis_cli.set_once_token("email", &id, *CONFIRM_EXPIRE_TIME).await?;
+    let link = format!("{}/api/v1/auth/email/confirm/{}", CONFIG.server.domain, token);
+    let token = redis_cli.set_once_token("email_cancel", &id, *EMAIL_CANCEL_TIME).await?;
+    let message = format!(
+        "Your account email is being changed to {}. If this was not you, cancel it within {} days: \
+         {}/api/v1/auth/email/cancel/{}",
+        data.email,
+        *EMAIL_CANCEL_TIME / (60 * 60 * 24),
+        CONFIG.server.domain,
+        token
+    );
+    // email_change 已写入，邮件在后台发送，失败只记录日志
+    task::spawn(async move {
+        let subject = "Change Email API TEST Email";
+        if let Err(e) = send_email(&data.email, subject, &link).await {
+            log::error!("Send change email confirm error: {}", e);
+        }
+        if let Err(e) = send_email(&user.email, subject, &message).await {
+            log::error!("Send change email notice error: {}", e);
+        }
+    });
+    Responser::new(Some("确认邮件已发送，请查收"), &status::OK).to_result()
+}
+
+async fn update_user(mongo: &MongoDb, id: &str, update: Document) -> tide::Result<i64> {
+    let mut opt = Options::default();
+    opt.update_opt(&USER, Some(doc! { "_id": ObjectId::with_string(id)? }), None);
+    mongo.update(update, opt).await
+}
+
+// 已开启两步验证时校验验证码，返回校验后的状态，失败时返回错误响应
+async fn check_mfa(
+    req: &mut tide::Request<State>,
+    id: &str,
+) -> tide::Result<Result<Mfa, tide::Response>> {
+    let data: MfaCode = req.body_json().await?;
+    let user = match find_user(&req.state().mongo, id).await? {
+        Some(user) => user,
+        None => {
+            let res = Responser::new(Some("帐号不存在"), &status::BAD_REQUEST).to_result()?;
+            return Ok(Err(res));
+        }
+    };
+    verify_mfa(req, &user, &data.code, &status::BAD_REQUEST).await
+}
+
+// 生成新的密钥，用第一个验证码确认后才生效
+pub async fn mfa_enroll(req: tide::Request<State>) -> tide::Result {
+    let user_id = match session_owner(&req) {
+        Some(id) => id,
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let mongo = &req.state().mongo;
+    let user = match find_user(mongo, &user_id).await? {
+        Some(user) => user,
+        None => return Responser::new(Some("帐号不存在"), &status::BAD_REQUEST).to_result(),
+    };
+    if user.mfa.map(|m| m.enabled).unwrap_or(false) {
+        return Responser::new(Some("两步验证已开启"), &status::BAD_REQUEST).to_result();
+    }
+
+    let secret = totp::new_secret();
+    let encrypted = match encrypt(&secret) {
+        Some(encrypted) => encrypted,
+        None => return Responser::new(Some("未配置 secret_key"), &status::SYS_ERROR).to_result(),
+    };
+    let mfa = Mfa {
+        secret: encrypted,
+        enabled: false,
+        recovery: Vec::new(),
+        last_step: 0,
+    };
+    update_user(mongo, &user_id, doc! { "$set": { "mfa": to_bson(&mfa)? } }).await?;
+    let res = ResMfaEnroll {
+        uri: totp::otpauth_uri(&secret, &user.email, "tide-server-example"),
+        secret,
+    };
+    Responser::new(Some(res), &status::OK).to_result()
+}
+
+// 确认后开启，返回只展示一次的恢复码
+pub async fn mfa_confirm(mut req: tide::Request<State>) -> tide::Result {
+    let data: MfaCode = req.body_json().await?;
+    if let Err(e) = data.validate() {
+        return Responser::new(Some(e), &status::BAD_REQUEST).to_result();
+    }
+    let user_id = match session_owner(&req) {
+        Some(id) => id,
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let mongo = &req.state().mongo;
+    let pending = find_user(mongo, &user_id)
+        .await?
+        .and_then(|u| u.mfa)
+        .filter(|m| !m.enabled);
+    let pending = match pending {
+        Some(mfa) => mfa,
+        None => {
+            return Responser::new(Some("请先获取两步验证密钥"), &status::BAD_REQUEST)
+                .to_result()
+        }
+    };
+    let now = Utc::now().timestamp() as u64;
+    let mut mfa = match pending.verify(&data.code, now) {
+        Some(mfa) => mfa,
+        None => return Responser::new(Some("验证码错误"), &status::BAD_REQUEST).to_result(),
+    };
+
+    let (codes, recovery) = Mfa::new_recovery()?;
+    mfa.enabled = true;
+    mfa.recovery = recovery;
+    update_user(mongo, &user_id, doc! { "$set": { "mfa": to_bson(&mfa)? } }).await?;
+    Responser::new(Some(codes), &status::OK).to_result()
+}
+
+pub async fn mfa_disable(mut req: tide::Request<State>) -> tide::Result {
+    let user_id = match session_owner(&req) {
+        Some(id) => id,
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    if let Err(res) = check_mfa(&mut req, &user_id).await? {
+        return Ok(res);
+    }
+    let mongo = &req.state().mongo;
+    update_user(mongo, &user_id, doc! { "$unset": { "mfa": "" } }).await?;
+    Responser::new(Some("success"), &status::OK).to_result()
+}
+
+// 重新生成恢复码，旧的全部失效
+pub async fn mfa_recovery(mut req: tide::Request<State>) -> tide::Result {
+    let user_id = match session_owner(&req) {
+        Some(id) => id,
+        None => return Responser::new(Some(""), &status::UNAUTH).to_result(),
+    };
+    let mut mfa = match check_mfa(&mut req, &user_id).await? {
+        Ok(mfa) => mfa,
+        Err(res) => return Ok(res),
+    };
+    let (codes, recovery) = Mfa::new_recovery()?;
+    mfa.recovery = recovery;
+    let mongo = &req.state().mongo;
+    update_user(mongo, &user_id, doc! { "$set": { "mfa": to_bson(&mfa)? } }).await?;
+    Responser::new(Some(codes), &status::OK).to_result()
+}
@@ -1,5 +1,6 @@
-use validator::Validate;
-use crate::utils::Page;
+use validator::{Validate, ValidationError};
+use crate::models::{ApiKey, Role};
+use crate::utils::{my_date_format, Page};
 use chrono::prelude::{DateTime, Local};
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -10,8 +11,103 @@ pub(crate) struct GetUser {
 
 #[derive(Deserialize, Serialize)]
 pub(crate) struct ResUser {
+    #[serde(skip_deserializing)]
+    pub(crate) id: String,
     username: String,
     email: String,
     phone: String,
+    #[serde(default)]
+    roles: Vec<Role>,
     create_at: DateTime<Local>,
-}
\ No newline at end of file
+}
+
+#[derive(Deserialize, Validate)]
+pub(crate) struct SetRoles {
+    #[validate(length(min = 1, message = "roles can not be empty"))]
+    pub(crate) roles: Vec<Role>,
+}
+
+// scope 为 *，或 资源:操作，操作可以为 *
+fn validate_scopes(scopes: &[String]) -> Result<(), ValidationError> {
+    let valid = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_lowercase());
+    let ok = scopes.iter().all(|s| match s.split_once(':') {
+        Some((resource, action)) => valid(resource) && (action == "*" || valid(action)),
+        None => s == "*",
+    });
+    if ok {
+        Ok(())
+    } else {
+        Err(ValidationError::new("scope format error"))
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub(crate) struct AddApiKey {
+    #[validate(length(min = 1, max = 64, message = "name length 1-64"))]
+    pub(crate) name: String,
+    #[validate(
+        length(min = 1, message = "scopes can not be empty"),
+        custom = "validate_scopes"
+    )]
+    pub(crate) scopes: Vec<String>,
+    // 有效天数，不传为永久有效
+    #[validate(range(min = 1, max = 365, message = "expire_days 1-365"))]
+    pub(crate) expire_days: Option<i64>,
+}
+
+// 不返回 hash
+#[derive(Serialize)]
+pub(crate) struct ResApiKey {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) prefix: String,
+    pub(crate) scopes: Vec<String>,
+    pub(crate) expire_at: Option<DateTime<Local>>,
+    pub(crate) last_used: Option<DateTime<Local>>,
+    #[serde(with = "my_date_format")]
+    pub(crate) create_at: DateTime<Local>,
+}
+
+impl ResApiKey {
+    pub(crate) fn new(id: String, key: ApiKey) -> Self {
+        Self {
+            id,
+            name: key.name,
+            prefix: key.prefix,
+            scopes: key.scopes,
+            expire_at: key.expire_at,
+            last_used: key.last_used,
+            create_at: key.create_at,
+        }
+    }
+}
+
+// 完整的 key 只在创建时返回一次
+#[derive(Serialize)]
+pub(crate) struct ResNewApiKey {
+    pub(crate) key: String,
+    #[serde(flatten)]
+    pub(crate) info: ResApiKey,
+}
+
+#[derive(Deserialize, Validate)]
+pub(crate) struct ChangeEmail {
+    #[validate(email(message = "email type error"))]
+    pub(crate) email: String,
+    // 修改邮箱需要验证当前密码
+    #[validate(length(min = 1, message = "password can not be empty"))]
+    pub(crate) password: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub(crate) struct MfaCode {
+    #[validate(length(min = 1, message = "code can not be empty"))]
+    pub(crate) code: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ResMfaEnroll {
+    pub(crate) secret: String,
+    // otpauth:// 格式，前端生成二维码
+    pub(crate) uri: String,
+}
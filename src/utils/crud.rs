@@ -0,0 +1,104 @@
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use serde::Serialize;
+use tide::Request;
+
+use super::{my_date_format, status, Page, Responser};
+use crate::db::Options;
+use crate::State;
+
+// 接口、步骤、用例和环境都挂在项目下，按 id 查询、修改、删除的处理相同
+
+// 路径中的项目 id 和资源 id 组成的查询条件，id 不合法时返回 Err(错误信息)
+pub(crate) fn project_filter(req: &Request<State>) -> tide::Result<Result<Document, String>> {
+    let project_id = req.param::<String>("pid")?;
+    let id = req.param::<String>("id")?;
+    Ok(match ObjectId::with_string(&id) {
+        Ok(oid) => Ok(doc! { "_id": oid, "project_id": project_id }),
+        Err(e) => Err(e.to_string()),
+    })
+}
+
+pub(crate) async fn get_one<T: Serialize>(
+    req: &Request<State>,
+    collect: &str,
+    to_res: fn(Document) -> Option<T>,
+    missing: &str,
+) -> tide::Result {
+    let filter = match project_filter(req)? {
+        Ok(filter) => filter,
+        Err(e) => return Responser::new(Some(e), &status::BAD_REQUEST).to_result(),
+    };
+
+    let mut opt = Options::default();
+    opt.find_one_opt(collect, Some(filter));
+    let mut res = req.state().mongo.find(opt).await?;
+    match res.pop().and_then(to_res) {
+        Some(data) => Responser::new(Some(data), &status::OK).to_result(),
+        None => Responser::new(Some(missing), &status::BAD_REQUEST).to_result(),
+    }
+}
+
+// condition 里已经带上了 project_id
+pub(crate) async fn list<T: Serialize>(
+    req: &Request<State>,
+    collect: &str,
+    condition: Document,
+    page: &Page,
+    to_res: fn(Document) -> Option<T>,
+) -> tide::Result {
+    let skip = (page.page_num.max(1) - 1) * page.page_size;
+    let opt = Options::new(
+        collect,
+        Some(condition),
+        Some(page.page_size as i64),
+        Some(skip as i64),
+        Some(doc! { "_id": -1 }),
+        None,
+    );
+    let data: Vec<T> = req
+        .state()
+        .mongo
+        .find(opt)
+        .await?
+        .into_iter()
+        .filter_map(to_res)
+        .collect();
+    Responser::new(Some(data), &status::OK).to_result()
+}
+
+// set 是要修改的字段，update_at 在这里统一写入
+pub(crate) async fn update_one(
+    req: &Request<State>,
+    collect: &str,
+    mut set: Document,
+    missing: &str,
+) -> tide::Result {
+    let filter = match project_filter(req)? {
+        Ok(filter) => filter,
+        Err(e) => return Responser::new(Some(e), &status::BAD_REQUEST).to_result(),
+    };
+
+    set.insert("update_at", my_date_format::now());
+    let mut opt = Options::default();
+    opt.update_opt(collect, Some(filter), None);
+    let count = req.state().mongo.update(doc! { "$set": set }, opt).await?;
+    if count == 0 {
+        return Responser::new(Some(missing), &status::BAD_REQUEST).to_result();
+    }
+    Responser::new(Some(req.param::<String>("id")?), &status::OK).to_result()
+}
+
+pub(crate) async fn delete_one(req: &Request<State>, collect: &str, missing: &str) -> tide::Result {
+    let filter = match project_filter(req)? {
+        Ok(filter) => filter,
+        Err(e) => return Responser::new(Some(e), &status::BAD_REQUEST).to_result(),
+    };
+
+    let mut opt = Options::default();
+    opt.del_opt(collect, Some(filter), None);
+    let count = req.state().mongo.delete(opt).await?;
+    if count == 0 {
+        return Responser::new(Some(missing), &status::BAD_REQUEST).to_result();
+    }
+    Responser::new(Some(req.param::<String>("id")?), &status::OK).to_result()
+}
@@ -1,15 +1,23 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
 use argon2::{self, Config, ThreadMode, Variant, Version};
-use base64::encode;
+use base64::{decode, decode_config, encode, STANDARD_NO_PAD};
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 
+use crate::setting::Argon2;
+use crate::CONFIG;
+
 lazy_static! {
-    static ref CRYPTO_MEM: u32 = 4096;
-    static ref CRYPTO_TIME: u32 = 10;
-    static ref CRYPTO_LANES: u32 = 4;
-    static ref CRYPTO_LEN: u32 = 32;
-    static ref SALT_LEN: usize = 12;
+    // 未配置 secret_key 时为 None，用到加密时才报错；格式错误在启动时由 check_secret_key 拦下
+    static ref CIPHER: Option<Aes256Gcm> = new_cipher(&CONFIG.secret.secret_key).ok().flatten();
 }
 
+// 旧格式 salt:hash 使用的固定参数，只用于校验
+const LEGACY_MEM: u32 = 4096;
+const LEGACY_TIME: u32 = 10;
+const LEGACY_LANES: u32 = 4;
+const LEGACY_SALT_LEN: usize = 12;
+
 pub fn rand_str(length: usize) -> String {
     thread_rng()
         .sample_iter(&Alphanumeric)
@@ -18,63 +26,194 @@ pub fn rand_str(length: usize) -> String {
         .collect()
 }
 
-pub fn hash_password(password: &str) -> String {
-    let salt = rand_str(*SALT_LEN);
-    let pwd_byte = password.as_bytes();
-    let salt_byte = salt.as_bytes();
-    let config = Config {
+fn argon2_config(params: &Argon2) -> Config<'static> {
+    Config {
         ad: &[],
-        hash_length: *CRYPTO_LEN,
-        lanes: *CRYPTO_LANES,
-        mem_cost: *CRYPTO_MEM,
+        hash_length: params.hash_len,
+        lanes: params.lanes,
+        mem_cost: params.mem_cost,
         secret: &[],
         thread_mode: ThreadMode::Parallel,
-        time_cost: *CRYPTO_TIME,
+        time_cost: params.time_cost,
         variant: Variant::Argon2id,
         version: Version::Version13,
-    };
-    let hash = argon2::hash_encoded(pwd_byte, salt_byte, &config).expect("Password Hash Error");
-    let hash = hash.split("$").last().expect("Password Not get Hash Value");
-    format!("{}:{}", salt, hash)
+    }
+}
+
+// 返回 PHC 格式，参数随 hash 一起保存
+pub fn hash_password(password: &str) -> Result<String, argon2::Error> {
+    hash_with(password, &CONFIG.argon2)
+}
+
+// 启动时检查 argon2 参数，参数不合法时拒绝启动
+pub fn check_argon2() -> Result<(), String> {
+    hash_with("", &CONFIG.argon2)
+        .map(|_| ())
+        .map_err(|e| format!("argon2: {}", e))
+}
+
+fn hash_with(password: &str, params: &Argon2) -> Result<String, argon2::Error> {
+    let mut rng = thread_rng();
+    let salt: Vec<u8> = (0..params.salt_len).map(|_| rng.gen()).collect();
+    argon2::hash_encoded(password.as_bytes(), &salt, &argon2_config(params))
 }
 
 pub fn password_verify(password_hash: &str, password: &str) -> bool {
-    let pwd_byte = password.as_bytes();
-    let hash_pwd = password_hash.split(':').collect::<Vec<&str>>();
-    let salt = match hash_pwd.get(0) {
-        None => return false,
-        Some(s) => {
-            if s.len() != *SALT_LEN {
-                return false;
-            } else {
-                encode(s).replace("==", "")
-            }
+    let encoded = if password_hash.starts_with('$') {
+        password_hash.to_string()
+    } else {
+        match legacy_phc(password_hash) {
+            Some(encoded) => encoded,
+            None => return false,
         }
     };
-    let hash = match hash_pwd.get(1) {
-        None => return false,
-        Some(s) => s,
-    };
-    let pwd_hash = format!(
+    argon2::verify_encoded(&encoded, password.as_bytes()).unwrap_or(false)
+}
+
+// 旧格式 salt:hash 还原为 PHC 字符串
+fn legacy_phc(password_hash: &str) -> Option<String> {
+    let (salt, hash) = password_hash.split_once(':')?;
+    if salt.len() != LEGACY_SALT_LEN {
+        return None;
+    }
+    Some(format!(
         "${}$v={}$m={},t={},p={}${}${}",
         Variant::Argon2id.as_lowercase_str(),
         Version::Version13.as_u32(),
-        *CRYPTO_MEM,
-        *CRYPTO_TIME,
-        *CRYPTO_LANES,
-        salt,
+        LEGACY_MEM,
+        LEGACY_TIME,
+        LEGACY_LANES,
+        encode(salt).replace("==", ""),
         hash
-    );
-    argon2::verify_encoded(&pwd_hash, pwd_byte).unwrap_or(false)
+    ))
+}
+
+// 旧格式或参数与当前配置不同时，登录成功后需要重新 hash
+pub fn needs_rehash(password_hash: &str) -> bool {
+    needs_rehash_with(password_hash, &CONFIG.argon2)
+}
+
+fn needs_rehash_with(password_hash: &str, params: &Argon2) -> bool {
+    let parts: Vec<&str> = password_hash.split('$').collect();
+    if parts.len() != 6 || parts[1] != Variant::Argon2id.as_lowercase_str() {
+        return true;
+    }
+    let decoded_len = |s: &str| decode_config(s, STANDARD_NO_PAD).map(|b| b.len()).ok();
+    parts[2] != format!("v={}", Version::Version13.as_u32())
+        || parts[3]
+            != format!(
+                "m={},t={},p={}",
+                params.mem_cost, params.time_cost, params.lanes
+            )
+        || decoded_len(parts[4]) != Some(params.salt_len)
+        || decoded_len(parts[5]) != Some(params.hash_len as usize)
+}
+
+// secret_key 为 base64 编码的 32 字节密钥，为空表示未配置
+fn new_cipher(secret_key: &str) -> Result<Option<Aes256Gcm>, String> {
+    if secret_key.is_empty() {
+        return Ok(None);
+    }
+    let key = decode(secret_key).map_err(|e| format!("secret_key: {}", e))?;
+    Aes256Gcm::new_from_slice(&key)
+        .map(Some)
+        .map_err(|_| String::from("secret_key: 需要 32 字节"))
+}
+
+// 启动时检查 secret_key，配置了但不可用时拒绝启动
+pub fn check_secret_key() -> Result<(), String> {
+    new_cipher(&CONFIG.secret.secret_key).map(|_| ())
+}
+
+// AES-256-GCM 加密，结果为 base64(nonce + 密文)，未配置 secret_key 时返回 None
+pub fn encrypt(plain: &str) -> Option<String> {
+    let cipher = CIPHER.as_ref()?;
+    let nonce: [u8; 12] = thread_rng().gen();
+    let mut data = nonce.to_vec();
+    let encrypted = cipher
+        .encrypt(Nonce::from_slice(&nonce), plain.as_bytes())
+        .ok()?;
+    data.extend(encrypted);
+    Some(encode(&data))
+}
+
+pub fn decrypt(data: &str) -> Option<String> {
+    let cipher = CIPHER.as_ref()?;
+    let data = decode(data).ok()?;
+    if data.len() < 12 {
+        return None;
+    }
+    let (nonce, encrypted) = data.split_at(12);
+    let plain = cipher.decrypt(Nonce::from_slice(nonce), encrypted).ok()?;
+    String::from_utf8(plain).ok()
 }
 
 #[cfg(test)]
 mod test {
-    use super::{hash_password, password_verify};
+    use argon2::Config;
+
+    use super::{
+        argon2_config, decrypt, encrypt, hash_password, hash_with, needs_rehash,
+        needs_rehash_with, new_cipher, password_verify, LEGACY_LANES, LEGACY_MEM, LEGACY_TIME,
+    };
+    use crate::setting::Argon2;
+    use crate::CONFIG;
 
     #[test]
     fn test_password() {
-        let pwd_hash = hash_password("123456");
-        assert_eq!(password_verify(&pwd_hash, "123456"), true);
+        let pwd_hash = hash_password("123456").unwrap();
+        assert!(pwd_hash.starts_with("$argon2id$v=19$"));
+        assert!(password_verify(&pwd_hash, "123456"));
+        assert!(!password_verify(&pwd_hash, "1234567"));
+        assert!(!needs_rehash(&pwd_hash));
+
+        // 旧格式 salt:hash
+        let salt = "abcdefghijkl";
+        let config = Config {
+            mem_cost: LEGACY_MEM,
+            time_cost: LEGACY_TIME,
+            lanes: LEGACY_LANES,
+            ..argon2_config(&CONFIG.argon2)
+        };
+        let encoded = argon2::hash_encoded(b"123456", salt.as_bytes(), &config).unwrap();
+        let legacy = format!("{}:{}", salt, encoded.rsplit('$').next().unwrap());
+        assert!(password_verify(&legacy, "123456"));
+        assert!(!password_verify(&legacy, "1234567"));
+        assert!(needs_rehash(&legacy));
+
+        // 调整参数后旧 hash 仍可校验，但需要升级
+        let tuned = Argon2 {
+            time_cost: 2,
+            ..CONFIG.argon2.clone()
+        };
+        assert!(needs_rehash_with(&pwd_hash, &tuned));
+        let rehashed = hash_with("123456", &tuned).unwrap();
+        assert!(!needs_rehash_with(&rehashed, &tuned));
+        assert!(password_verify(&rehashed, "123456"));
+
+        // 参数不合法时返回错误，不 panic
+        let broken = Argon2 {
+            salt_len: 4,
+            ..CONFIG.argon2.clone()
+        };
+        assert!(hash_with("123456", &broken).is_err());
+        let broken = Argon2 {
+            lanes: 0,
+            ..CONFIG.argon2.clone()
+        };
+        assert!(hash_with("123456", &broken).is_err());
+    }
+
+    #[test]
+    fn test_encrypt() {
+        let data = encrypt("secret value").unwrap();
+        assert_ne!(Some(data.clone()), encrypt("secret value"));
+        assert_eq!(decrypt(&data), Some(String::from("secret value")));
+        assert_eq!(decrypt("not encrypted"), None);
+
+        assert!(new_cipher("").unwrap().is_none());
+        assert!(new_cipher("not base64!").is_err());
+        assert!(new_cipher(&base64::encode([0u8; 16])).is_err());
+        assert!(new_cipher(&base64::encode([0u8; 32])).unwrap().is_some());
     }
 }
@@ -32,4 +32,43 @@ pub mod my_date_format {
         let s = String::deserialize(deserializer)?;
         Local.datetime_from_str(&s, FORMAT).map_err(serde::de::Error::custom)
     }
+
+    // 更新文档时写入的时间，和序列化保持同一格式
+    pub fn now() -> String {
+        format!("{}", Local::now().format(FORMAT))
+    }
+
+    // update_at 这类可选时间字段
+    pub mod option {
+        use chrono::{DateTime, Local, TimeZone};
+        use serde::{self, Deserialize, Serializer, Deserializer};
+
+        pub fn serialize<S>(
+            date: &Option<DateTime<Local>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match date {
+                Some(date) => super::serialize(date, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(
+            deserializer: D,
+        ) -> Result<Option<DateTime<Local>>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            match Option::<String>::deserialize(deserializer)? {
+                Some(s) => Local
+                    .datetime_from_str(&s, super::FORMAT)
+                    .map(Some)
+                    .map_err(serde::de::Error::custom),
+                None => Ok(None),
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -1,10 +1,17 @@
 mod crypto;
+pub(crate) mod crud;
 mod emailer;
 mod helper;
+mod jwt;
 mod responser;
 pub(crate) mod status;
+pub(crate) mod password;
+pub(crate) mod totp;
 
-pub(crate) use crypto::{hash_password, password_verify, rand_str};
+pub(crate) use crypto::{
+    check_argon2, check_secret_key, decrypt, encrypt, hash_password, needs_rehash, password_verify, rand_str,
+};
 pub(crate) use emailer::send_email;
 pub(crate) use responser::{responser, Responser};
 pub(crate) use helper::{Page, my_date_format};
+pub(crate) use jwt::{Claims, KeySet, KEYSET};
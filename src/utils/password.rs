@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+use std::fs;
+
+use tide::log;
+use validator::{ValidationError, ValidationErrors};
+
+use crate::setting::PasswordPolicy;
+use crate::CONFIG;
+
+// 内置的常见弱密码列表
+const COMMON_PASSWORDS: &str = include_str!("../../config/common_passwords.txt");
+
+lazy_static! {
+    static ref BREACHED: HashSet<String> = load_breached(&CONFIG.password);
+}
+
+fn load_breached(policy: &PasswordPolicy) -> HashSet<String> {
+    let mut list = parse_list(COMMON_PASSWORDS);
+    if let Some(path) = &policy.breached_file {
+        match fs::read_to_string(path) {
+            Ok(text) => list.extend(parse_list(&text)),
+            Err(e) => log::error!("Load breached password file {} error: {}", path, e),
+        }
+    }
+    list
+}
+
+fn parse_list(text: &str) -> HashSet<String> {
+    text.lines()
+        .map(|l| l.trim().to_lowercase())
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+fn error(code: &'static str, message: String) -> ValidationError {
+    let mut e = ValidationError::new(code);
+    e.message = Some(message.into());
+    e
+}
+
+// identities 为用户名、邮箱等，密码中不能包含
+fn check(
+    password: &str,
+    identities: &[&str],
+    policy: &PasswordPolicy,
+    breached: &HashSet<String>,
+) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    if password.chars().count() < policy.min_length {
+        let msg = format!("password length min {}", policy.min_length);
+        errors.push(error("password_length", msg));
+    }
+    let classes: [(bool, fn(&char) -> bool, &str); 4] = [
+        (policy.require_lower, |c| c.is_ascii_lowercase(), "lowercase letter"),
+        (policy.require_upper, |c| c.is_ascii_uppercase(), "uppercase letter"),
+        (policy.require_digit, |c| c.is_ascii_digit(), "digit"),
+        (policy.require_symbol, |c| !c.is_alphanumeric(), "symbol"),
+    ];
+    for (required, matches, name) in classes.iter() {
+        if *required && !password.chars().any(|c| matches(&c)) {
+            let msg = format!("password must contain a {}", name);
+            errors.push(error("password_class", msg));
+        }
+    }
+
+    let lower = password.to_lowercase();
+    let contains = identities.iter().any(|i| {
+        // 邮箱只比较 @ 前面的部分
+        let i = i.split('@').next().unwrap_or("").to_lowercase();
+        i.len() >= 3 && lower.contains(&i)
+    });
+    if contains {
+        let msg = String::from("password can not contain username or email");
+        errors.push(error("password_identity", msg));
+    }
+    if breached.contains(&lower) {
+        let msg = String::from("password is too common");
+        errors.push(error("password_breached", msg));
+    }
+    errors
+}
+
+fn into_errors(errors: Vec<ValidationError>) -> Result<(), ValidationErrors> {
+    let mut res = ValidationErrors::new();
+    for e in errors {
+        res.add("password", e);
+    }
+    if res.is_empty() {
+        Ok(())
+    } else {
+        Err(res)
+    }
+}
+
+// 按配置的密码策略校验，错误格式与 Validate 一致
+pub(crate) fn validate(password: &str, identities: &[&str]) -> Result<(), ValidationErrors> {
+    into_errors(check(password, identities, &CONFIG.password, &BREACHED))
+}
+
+pub(crate) fn reused() -> ValidationErrors {
+    let msg = format!(
+        "password can not be the same as the last {}",
+        CONFIG.password.history
+    );
+    into_errors(vec![error("password_reused", msg)]).unwrap_err()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, parse_list, COMMON_PASSWORDS};
+    use crate::setting::PasswordPolicy;
+
+    #[test]
+    fn test_check() {
+        let policy = PasswordPolicy::default();
+        let breached = parse_list(COMMON_PASSWORDS);
+        let codes = |password: &str| -> Vec<String> {
+            check(password, &["lomect", "Lomect@example.com"], &policy, &breached)
+                .into_iter()
+                .map(|e| e.code.to_string())
+                .collect()
+        };
+
+        assert!(codes("Tide2Server").is_empty());
+        assert_eq!(codes("Ab1"), vec!["password_length"]);
+        assert_eq!(codes("tideserver2"), vec!["password_class"]);
+        assert_eq!(codes("tideserver"), vec!["password_class", "password_class"]);
+        assert_eq!(codes("MyLOMECT2020"), vec!["password_identity"]);
+        assert_eq!(codes("Password123"), vec!["password_breached"]);
+
+        let policy = PasswordPolicy {
+            require_symbol: true,
+            ..PasswordPolicy::default()
+        };
+        assert_eq!(check("Tide2Server", &[], &policy, &breached).len(), 1);
+        assert!(check("Tide2Server!", &[], &policy, &breached).is_empty());
+    }
+}
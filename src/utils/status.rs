@@ -6,5 +6,8 @@ lazy_static! {
     pub(crate) static ref BAD_REQUEST: Res = (1011, String::from("bad request"));
     pub(crate) static ref UNAUTH: Res = (1012, String::from("Unauthorized"));
     pub(crate) static ref TIME_OUT: Res = (1013, String::from("TIME_OUT"));
+    pub(crate) static ref FORBIDDEN: Res = (1014, String::from("Forbidden"));
+    pub(crate) static ref MFA_REQUIRED: Res = (1015, String::from("MFA Required"));
+    pub(crate) static ref TOO_MANY: Res = (1016, String::from("Too Many Requests"));
     pub(crate) static ref UNKNOWN: Res = (1020, String::from("UNKNOWN"));
 }
@@ -0,0 +1,95 @@
+use data_encoding::BASE32_NOPAD;
+use rand::{thread_rng, Rng};
+use ring::hmac;
+
+// RFC 6238，30 秒一个周期，6 位数字
+const STEP: u64 = 30;
+const DIGITS: u32 = 6;
+// 允许前后各一个周期的时钟误差
+const SKEW: u64 = 1;
+
+// base32 编码的 160 位随机密钥
+pub fn new_secret() -> String {
+    let bytes: [u8; 20] = thread_rng().gen();
+    BASE32_NOPAD.encode(&bytes)
+}
+
+// 给认证器 App 扫码用
+pub fn otpauth_uri(secret: &str, account: &str, issuer: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        encode(issuer),
+        encode(account),
+        secret,
+        encode(issuer),
+        DIGITS,
+        STEP
+    )
+}
+
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'@' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn code_at(key: &[u8], counter: u64) -> u32 {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, key);
+    let tag = hmac::sign(&key, &counter.to_be_bytes());
+    let hash = tag.as_ref();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    value % 10u32.pow(DIGITS)
+}
+
+// 校验成功返回匹配的周期，只接受大于 last_step 的周期，防止同一个 code 重复使用
+pub fn verify(secret: &str, code: &str, now: u64, last_step: u64) -> Option<u64> {
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    if code.len() != DIGITS as usize {
+        return None;
+    }
+    let code = code.parse::<u32>().ok()?;
+    let current = now / STEP;
+    (current.saturating_sub(SKEW)..=current + SKEW)
+        .filter(|step| *step > last_step)
+        .find(|step| code_at(&key, *step) == code)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{code_at, new_secret, otpauth_uri, verify};
+
+    #[test]
+    fn test_totp() {
+        // RFC 6238 附录 B 的 SHA1 测试数据，取后 6 位
+        let key = b"12345678901234567890";
+        assert_eq!(code_at(key, 59 / 30), 287082);
+        assert_eq!(code_at(key, 1111111109 / 30), 81804);
+        assert_eq!(code_at(key, 2000000000 / 30), 279037);
+
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        assert_eq!(verify(secret, "287082", 59, 0), Some(1));
+        assert_eq!(verify(secret, "287082", 89, 0), Some(1));
+        assert_eq!(verify(secret, "287082", 59, 1), None);
+        assert_eq!(verify(secret, "287083", 59, 0), None);
+        assert_eq!(verify(secret, "081804", 1111111109, 0), Some(37037036));
+        assert_eq!(verify(secret, "81804", 1111111109, 0), None);
+
+        assert_eq!(new_secret().len(), 32);
+        assert_eq!(
+            otpauth_uri("ABC", "a b@qq.com", "Tide Server"),
+            "otpauth://totp/Tide%20Server:a%20b@qq.com?secret=ABC&issuer=Tide%20Server\
+             &algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}